@@ -9,7 +9,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     add_jobs(&mut client).await?;
 
-    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    let now = time::OffsetDateTime::now_utc();
     add_jobs_at(&mut client, now).await?;
 
     Ok(())
@@ -29,14 +29,14 @@ async fn add_jobs(client: &mut AsyncClient) -> Result<(), Box<dyn std::error::Er
 
 async fn add_jobs_at(
     client: &mut AsyncClient,
-    run_at: i64,
+    run_at: time::OffsetDateTime,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let hello_job10 = HelloAsyncJob::new(10, "bob-10".into());
     client.schedule_at(&hello_job10, run_at).await?;
     let hello_job11 = HelloAsyncJob::new(11, "bob-11".into());
     client.schedule_at(&hello_job11, run_at).await?;
 
-    let next_run_at = run_at + 10;
+    let next_run_at = run_at + std::time::Duration::from_secs(10);
     let hello_job12 = HelloAsyncJob::new(12, "bob-12".into());
     client.schedule_at(&hello_job12, next_run_at).await?;
 
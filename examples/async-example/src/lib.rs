@@ -2,7 +2,7 @@ use async_trait::async_trait;
 use redis::{aio::ConnectionManager, AsyncCommands};
 use serde::{Deserialize, Serialize};
 use yq::{Job, JobType};
-use yq_async::AsyncJob;
+use yq_async::{AsyncJob, AsyncJobContext};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct HelloAsyncJob {
@@ -32,18 +32,24 @@ impl HelloAsyncState {
 impl Job for HelloAsyncJob {
     const JOB_TYPE: JobType = JobType::Borrowed("HelloAsyncJob");
     type State = HelloAsyncState;
+    type Output = ();
 }
 
 #[async_trait]
 impl AsyncJob for HelloAsyncJob {
-    async fn execute_async(self, mid: i64, mut state: Self::State) -> Result<(), String> {
+    async fn execute_async(
+        self,
+        ctx: AsyncJobContext,
+        mut state: Self::State,
+    ) -> Result<Self::Output, String> {
         let keys: Vec<String> = state
             .connection_manager
             .keys("*")
             .await
             .map_err(|err| err.to_string())?;
         println!(
-            "HelloAsyncJob.execute_async: mid={mid}, keys={}",
+            "HelloAsyncJob.execute_async: mid={}, keys={}",
+            ctx.mid(),
             keys.len()
         );
         Ok(())
@@ -8,7 +8,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     add_jobs(&mut client)?;
 
-    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    let now = time::OffsetDateTime::now_utc();
     add_jobs_at(&mut client, now)?;
 
     Ok(())
@@ -26,13 +26,16 @@ fn add_jobs(client: &mut SyncClient) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn add_jobs_at(client: &mut SyncClient, run_at: i64) -> Result<(), Box<dyn std::error::Error>> {
+fn add_jobs_at(
+    client: &mut SyncClient,
+    run_at: time::OffsetDateTime,
+) -> Result<(), Box<dyn std::error::Error>> {
     let hello_job10 = HelloSyncJob::new(10, "bob-10".into());
     client.schedule_at(&hello_job10, run_at)?;
     let hello_job11 = HelloSyncJob::new(11, "bob-11".into());
     client.schedule_at(&hello_job11, run_at)?;
 
-    let next_run_at = run_at + 10;
+    let next_run_at = run_at + std::time::Duration::from_secs(10);
     let hello_job12 = HelloSyncJob::new(12, "bob-12".into());
     client.schedule_at(&hello_job12, next_run_at)?;
 
@@ -1,7 +1,7 @@
 use redis::Commands;
 use serde::{Deserialize, Serialize};
 use yq::{Job, JobType};
-use yq_sync::SyncJob;
+use yq_sync::{SyncJob, SyncJobContext};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct HelloSyncJob {
@@ -31,15 +31,20 @@ impl HelloSyncState {
 impl Job for HelloSyncJob {
     const JOB_TYPE: JobType = JobType::Borrowed("HelloSyncJob");
     type State = HelloSyncState;
+    type Output = ();
 }
 
 impl SyncJob for HelloSyncJob {
-    fn execute(self, mid: i64, mut state: Self::State) -> Result<(), String> {
+    fn execute(self, ctx: SyncJobContext, mut state: Self::State) -> Result<Self::Output, String> {
         let keys: Vec<String> = state
             .redis_client
             .keys("*")
             .map_err(|err| err.to_string())?;
-        println!("HelloSyncJob.execute: mid={mid}, keys={}", keys.len());
+        println!(
+            "HelloSyncJob.execute: mid={}, keys={}",
+            ctx.mid(),
+            keys.len()
+        );
         Ok(())
     }
 }
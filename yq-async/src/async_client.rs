@@ -1,13 +1,28 @@
 use redis::aio::ConnectionManager;
+use std::time::Duration;
+use time::OffsetDateTime;
 use yq::{
-    EnqueueAction, EnqueueAtAction, EnqueueAtStatus, EnqueueStatus, Job, Queue, YqError, YqResult,
+    unix_timestamp_ms, BatchAction, BatchStatus, CancelAction, CancelStatus, DeadJob, DeadJobs,
+    DeadLetterAction, EnqueueAction, EnqueueAtAction, EnqueueAtStatus, EnqueueManyAction,
+    EnqueueOptions, EnqueueStatus, Job, JobStatus, PeriodicAction, PeriodicJob, PreparedJob, Queue,
+    RequeueStatus, ResultAction, SealBatchStatus, StatusAction, YqError, YqResult,
 };
 
+const WAIT_RESULT_MIN_INTERVAL: Duration = Duration::from_millis(50);
+const WAIT_RESULT_MAX_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Clone)]
 pub struct AsyncClient {
     connection_manager: ConnectionManager,
     enqueue_action: EnqueueAction,
     enqueue_at_action: EnqueueAtAction,
+    enqueue_many_action: EnqueueManyAction,
+    status_action: StatusAction,
+    cancel_action: CancelAction,
+    dead_letter_action: DeadLetterAction,
+    periodic_action: PeriodicAction,
+    result_action: ResultAction,
+    batch_action: BatchAction,
 }
 
 impl AsyncClient {
@@ -21,21 +36,35 @@ impl AsyncClient {
         Ok(Self {
             connection_manager,
             enqueue_action: EnqueueAction::new(queue.clone()),
-            enqueue_at_action: EnqueueAtAction::new(queue),
+            enqueue_at_action: EnqueueAtAction::new(queue.clone()),
+            enqueue_many_action: EnqueueManyAction::new(queue.clone()),
+            status_action: StatusAction::new(queue.clone()),
+            cancel_action: CancelAction::new(queue.clone()),
+            dead_letter_action: DeadLetterAction::new(queue.clone()),
+            periodic_action: PeriodicAction::new(queue.clone()),
+            result_action: ResultAction::new(queue.clone()),
+            batch_action: BatchAction::new(queue),
         })
     }
 
     pub async fn schedule<J: Job>(&self, job: &J) -> YqResult<i64> {
+        self.schedule_with(job, &EnqueueOptions::default()).await
+    }
+
+    pub async fn schedule_with<J: Job>(&self, job: &J, options: &EnqueueOptions) -> YqResult<i64> {
         let mut redis_conn = self.connection_manager.clone();
         let enqueue_status: EnqueueStatus = self
             .enqueue_action
-            .prepare_invoke(job)?
+            .prepare_invoke(job, options)?
             .invoke_async(&mut redis_conn)
             .await
             .map_err(YqError::Enqueue)?;
 
         match enqueue_status {
             EnqueueStatus::Added(added) => Ok(added.mid),
+            EnqueueStatus::Exists(mid) => Ok(mid),
+            EnqueueStatus::Waiting(mid) => Ok(mid),
+            EnqueueStatus::ParentFailed(parent) => Err(YqError::ParentFailed(parent)),
             EnqueueStatus::Unknown(err) => Err(YqError::Enqueue(redis::RedisError::from((
                 redis::ErrorKind::ResponseError,
                 "enqueue error",
@@ -44,17 +73,43 @@ impl AsyncClient {
         }
     }
 
-    pub async fn schedule_at<J: Job>(&self, job: &J, run_at: i64) -> YqResult<i64> {
+    pub async fn schedule_in<J: Job>(&self, job: &J, delay: Duration) -> YqResult<i64> {
+        self.schedule_in_with(job, delay, &EnqueueOptions::default())
+            .await
+    }
+
+    pub async fn schedule_in_with<J: Job>(
+        &self,
+        job: &J,
+        delay: Duration,
+        options: &EnqueueOptions,
+    ) -> YqResult<i64> {
+        self.schedule_at_with(job, OffsetDateTime::now_utc() + delay, options)
+            .await
+    }
+
+    pub async fn schedule_at<J: Job>(&self, job: &J, run_at: OffsetDateTime) -> YqResult<i64> {
+        self.schedule_at_with(job, run_at, &EnqueueOptions::default())
+            .await
+    }
+
+    pub async fn schedule_at_with<J: Job>(
+        &self,
+        job: &J,
+        run_at: OffsetDateTime,
+        options: &EnqueueOptions,
+    ) -> YqResult<i64> {
         let mut redis_conn = self.connection_manager.clone();
         let enqueue_at_status: EnqueueAtStatus = self
             .enqueue_at_action
-            .prepare_invoke(job, run_at)?
+            .prepare_invoke(job, unix_timestamp_ms(run_at), options)?
             .invoke_async(&mut redis_conn)
             .await
             .map_err(YqError::EnqueueAt)?;
 
         match enqueue_at_status {
             EnqueueAtStatus::Added(mid) => Ok(mid),
+            EnqueueAtStatus::Exists(mid) => Ok(mid),
             EnqueueAtStatus::Unknown(err) => Err(YqError::Enqueue(redis::RedisError::from((
                 redis::ErrorKind::ResponseError,
                 "enqueue error",
@@ -62,4 +117,271 @@ impl AsyncClient {
             )))),
         }
     }
+
+    // One round trip for the whole batch, returns the mids in job order
+    pub async fn schedule_many(&self, jobs: &[PreparedJob]) -> YqResult<Vec<i64>> {
+        if jobs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut redis_conn = self.connection_manager.clone();
+        self.enqueue_many_action
+            .prepare_invoke(jobs)
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(YqError::EnqueueMany)
+    }
+
+    pub async fn schedule_at_many(
+        &self,
+        jobs: &[(PreparedJob, OffsetDateTime)],
+    ) -> YqResult<Vec<i64>> {
+        if jobs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut redis_conn = self.connection_manager.clone();
+        self.enqueue_many_action
+            .prepare_invoke_at(
+                jobs.iter()
+                    .map(|(job, run_at)| (job, unix_timestamp_ms(*run_at))),
+            )
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(YqError::EnqueueMany)
+    }
+
+    // Jobs join with `schedule_in_batch` until the batch is sealed
+    pub async fn create_batch(&self) -> YqResult<i64> {
+        let mut redis_conn = self.connection_manager.clone();
+        self.batch_action
+            .prepare_create()
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(YqError::Batch)
+    }
+
+    // A unique duplicate returns the existing mid, which does not join the batch
+    pub async fn schedule_in_batch(
+        &self,
+        batch_id: i64,
+        jobs: &[PreparedJob],
+    ) -> YqResult<Vec<i64>> {
+        if jobs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut redis_conn = self.connection_manager.clone();
+        self.enqueue_many_action
+            .prepare_invoke_in_batch(batch_id, jobs)
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(|err| match err.code() {
+                Some("BATCH_NOT_OPEN") => YqError::BatchNotOpen(batch_id),
+                _ => YqError::EnqueueMany(err),
+            })
+    }
+
+    // The callback becomes ready once every job of the batch is done, dead or
+    // cancelled. Returns the callback mid.
+    pub async fn seal_batch(
+        &self,
+        batch_id: i64,
+        callback: Option<&PreparedJob>,
+    ) -> YqResult<Option<i64>> {
+        let mut redis_conn = self.connection_manager.clone();
+        let now = time::OffsetDateTime::now_utc();
+        let seal_status: SealBatchStatus = self
+            .batch_action
+            .prepare_seal(batch_id, callback, unix_timestamp_ms(now))
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(YqError::Batch)?;
+
+        match seal_status {
+            SealBatchStatus::Sealed(callback) => Ok(callback),
+            SealBatchStatus::Completed(callback) => Ok(callback),
+            SealBatchStatus::NotOpen => Err(YqError::BatchNotOpen(batch_id)),
+            SealBatchStatus::Unknown(err) => Err(YqError::Batch(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "seal batch error",
+                err,
+            )))),
+        }
+    }
+
+    pub async fn batch_status(&self, batch_id: i64) -> YqResult<BatchStatus> {
+        let mut redis_conn = self.connection_manager.clone();
+        self.batch_action
+            .prepare_status(batch_id)
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(YqError::Batch)
+    }
+
+    pub async fn status(&self, mid: i64) -> YqResult<JobStatus> {
+        let mut redis_conn = self.connection_manager.clone();
+        let now = time::OffsetDateTime::now_utc();
+        self.status_action
+            .prepare_invoke(mid, unix_timestamp_ms(now))
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(YqError::Status)
+    }
+
+    // None until the job is done, once the result ttl passed, or for a `null`
+    // output
+    pub async fn result<J: Job>(&self, mid: i64) -> YqResult<Option<J::Output>> {
+        let mut redis_conn = self.connection_manager.clone();
+        let result: Option<String> = self
+            .result_action
+            .prepare_get(mid)
+            .query_async(&mut redis_conn)
+            .await
+            .map_err(YqError::Result)?;
+
+        result
+            .map(|result| ResultAction::decode::<J>(&result))
+            .transpose()
+    }
+
+    // None when the job will not produce a result - dead, cancelled or the
+    // result already expired
+    pub async fn wait_for_result<J: Job>(
+        &self,
+        mid: i64,
+        timeout: Duration,
+    ) -> YqResult<Option<J::Output>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut interval = WAIT_RESULT_MIN_INTERVAL;
+
+        loop {
+            if let Some(output) = self.result::<J>(mid).await? {
+                return Ok(Some(output));
+            }
+
+            match self.status(mid).await? {
+                JobStatus::Done | JobStatus::Dead | JobStatus::Cancelled | JobStatus::Missing => {
+                    // Finished between the two reads
+                    return self.result::<J>(mid).await;
+                }
+                _ => {}
+            }
+
+            let now = tokio::time::Instant::now();
+            if now >= deadline {
+                return Err(YqError::WaitResultTimeout(mid));
+            }
+
+            tokio::time::sleep(interval.min(deadline - now)).await;
+            interval = (interval * 2).min(WAIT_RESULT_MAX_INTERVAL);
+        }
+    }
+
+    pub async fn cancel(&self, mid: i64) -> YqResult<CancelStatus> {
+        let mut redis_conn = self.connection_manager.clone();
+        let now = time::OffsetDateTime::now_utc();
+        self.cancel_action
+            .prepare_invoke(mid, unix_timestamp_ms(now))
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(YqError::Cancel)
+    }
+
+    pub async fn dead_jobs(&self, offset: usize, count: usize) -> YqResult<Vec<DeadJob>> {
+        let mut redis_conn = self.connection_manager.clone();
+        let dead_jobs: DeadJobs = self
+            .dead_letter_action
+            .prepare_list(offset, count)
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(YqError::DeadLetter)?;
+
+        Ok(dead_jobs.0)
+    }
+
+    pub async fn dead_job(&self, mid: i64) -> YqResult<Option<DeadJob>> {
+        let mut redis_conn = self.connection_manager.clone();
+        let dead_jobs: DeadJobs = self
+            .dead_letter_action
+            .prepare_inspect(mid)
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(YqError::DeadLetter)?;
+
+        Ok(dead_jobs.0.into_iter().next())
+    }
+
+    pub async fn requeue_dead(&self, mid: i64) -> YqResult<bool> {
+        let mut redis_conn = self.connection_manager.clone();
+        let requeue_status: RequeueStatus = self
+            .dead_letter_action
+            .prepare_requeue(mid)
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(YqError::DeadLetter)?;
+
+        match requeue_status {
+            RequeueStatus::Requeued => Ok(true),
+            RequeueStatus::Missing => Ok(false),
+            RequeueStatus::Unknown(err) => Err(YqError::DeadLetter(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "requeue error",
+                err,
+            )))),
+        }
+    }
+
+    pub async fn purge_dead(&self, mid: i64) -> YqResult<bool> {
+        let mut redis_conn = self.connection_manager.clone();
+        let count: i64 = self
+            .dead_letter_action
+            .prepare_purge(mid)
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(YqError::DeadLetter)?;
+
+        Ok(count > 0)
+    }
+
+    pub async fn purge_all_dead(&self) -> YqResult<i64> {
+        let mut redis_conn = self.connection_manager.clone();
+        self.dead_letter_action
+            .prepare_purge_all()
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(YqError::DeadLetter)
+    }
+
+    // Materialized by yq-scheduler, a job of the same name is replaced
+    pub async fn register_periodic(&self, periodic: &PeriodicJob) -> YqResult<()> {
+        let mut redis_conn = self.connection_manager.clone();
+        let now = time::OffsetDateTime::now_utc();
+        self.periodic_action
+            .prepare_register(periodic, unix_timestamp_ms(now))?
+            .query_async(&mut redis_conn)
+            .await
+            .map_err(YqError::Periodic)
+    }
+
+    pub async fn remove_periodic(&self, name: &str) -> YqResult<bool> {
+        let mut redis_conn = self.connection_manager.clone();
+        let (count,): (i64,) = self
+            .periodic_action
+            .prepare_remove(name)
+            .query_async(&mut redis_conn)
+            .await
+            .map_err(YqError::Periodic)?;
+
+        Ok(count > 0)
+    }
+
+    pub async fn periodic_jobs(&self) -> YqResult<Vec<PeriodicJob>> {
+        let mut redis_conn = self.connection_manager.clone();
+        self.periodic_action
+            .prepare_list()
+            .query_async(&mut redis_conn)
+            .await
+            .map_err(YqError::Periodic)
+    }
 }
@@ -1,48 +1,91 @@
+use crate::AsyncJobContext;
 use async_trait::async_trait;
 use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
-use yq::{decode_job, Job, JobType, YqError, YqResult, YqRunJobError};
+use std::time::Duration;
+use yq::{decode_job, Job, JobType, RetryPolicy, YqError, YqResult};
 
 #[async_trait]
-pub trait AsyncJob: Job + 'static + Send {
-    async fn execute_async(self, mid: i64, state: Self::State) -> Result<(), String>;
+pub trait AsyncJob: Job<Output: Send> + 'static + Send {
+    async fn execute_async(
+        self,
+        ctx: AsyncJobContext,
+        state: Self::State,
+    ) -> Result<Self::Output, String>;
 }
 
+// Resolves to the serialized `Job::Output`
 type AsyncJobFn<S> = Arc<
-    dyn Fn(i64, String, S) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>>
+    dyn Fn(AsyncJobContext, String, S) -> Pin<Box<dyn Future<Output = YqResult<String>> + Send>>
         + Send
         + Sync,
 >;
 
-pub(crate) struct AsyncJobFns<S>(HashMap<JobType, AsyncJobFn<S>>);
+struct AsyncJobEntry<S> {
+    job_fn: AsyncJobFn<S>,
+    retry_policy: RetryPolicy,
+    timeout: Option<Duration>,
+}
+
+pub(crate) struct AsyncJobFns<S>(HashMap<JobType, AsyncJobEntry<S>>);
 
 impl<S> AsyncJobFns<S> {
     pub(crate) fn new() -> AsyncJobFns<S> {
         AsyncJobFns::<S>(HashMap::default())
     }
 
-    pub(crate) fn reg_job(&mut self, job_type: JobType, job_fn: AsyncJobFn<S>) -> YqResult<()> {
-        if self.0.insert(job_type.clone(), job_fn).is_some() {
+    pub(crate) fn reg_job(
+        &mut self,
+        job_type: JobType,
+        job_fn: AsyncJobFn<S>,
+        retry_policy: RetryPolicy,
+        timeout: Option<Duration>,
+    ) -> YqResult<()> {
+        let entry = AsyncJobEntry {
+            job_fn,
+            retry_policy,
+            timeout,
+        };
+        if self.0.insert(job_type.clone(), entry).is_some() {
             Err(YqError::DupJobType(job_type))
         } else {
             Ok(())
         }
     }
 
-    pub(crate) async fn handle(&self, mid: i64, mcontent: String, state: S) -> YqResult<()> {
+    // `timeout` overrides the `Job::TIMEOUT` of the job type
+    pub(crate) async fn handle(
+        &self,
+        ctx: AsyncJobContext,
+        mcontent: String,
+        state: S,
+        timeout: Option<Duration>,
+    ) -> YqResult<String> {
         let (job_type, job_data) = decode_job(&mcontent)?;
 
-        let job_fn = match self.0.get(job_type) {
-            Some(job_fn) => job_fn,
+        let entry = match self.0.get(job_type) {
+            Some(entry) => entry,
             None => {
                 return Err(YqError::JobTypeMissing(JobType::from(job_type.to_string())));
             }
         };
 
-        job_fn(mid, job_data.to_string(), state)
-            .await
-            .map_err(|error| YqError::RunJobError(YqRunJobError::new(job_data.to_string(), error)))
+        let execution = (entry.job_fn)(ctx, job_data.to_string(), state);
+        match timeout.or(entry.timeout) {
+            Some(timeout) => tokio::time::timeout(timeout, execution)
+                .await
+                .map_err(|_elapsed| YqError::JobTimeout(timeout))?,
+            None => execution.await,
+        }
+    }
+
+    pub(crate) fn retry_policy(&self, mcontent: &str) -> RetryPolicy {
+        decode_job(mcontent)
+            .ok()
+            .and_then(|(job_type, _)| self.0.get(job_type))
+            .map(|entry| entry.retry_policy)
+            .unwrap_or_default()
     }
 }
@@ -0,0 +1,65 @@
+use redis::aio::ConnectionManager;
+use std::sync::Arc;
+use std::time::Duration;
+use yq::{unix_timestamp_ms, ExtendLockAction, ExtendLockStatus, YqError, YqResult};
+
+#[derive(Clone)]
+pub struct AsyncJobContext {
+    mid: i64,
+    token: i64,
+    attempts: i64,
+    connection_manager: ConnectionManager,
+    extend_lock_action: Arc<ExtendLockAction>,
+}
+
+impl AsyncJobContext {
+    pub(crate) fn new(
+        mid: i64,
+        token: i64,
+        attempts: i64,
+        connection_manager: ConnectionManager,
+        extend_lock_action: Arc<ExtendLockAction>,
+    ) -> Self {
+        Self {
+            mid,
+            token,
+            attempts,
+            connection_manager,
+            extend_lock_action,
+        }
+    }
+
+    pub fn mid(&self) -> i64 {
+        self.mid
+    }
+
+    // 1 on the first run
+    pub fn attempts(&self) -> i64 {
+        self.attempts
+    }
+
+    // Keep the mid locked for at least `lock` from now. False once the lock
+    // expired or was lost, the job may then be running on another worker.
+    pub async fn extend_lock(&self, lock: Duration) -> YqResult<bool> {
+        let mut redis_conn = self.connection_manager.clone();
+        let now = time::OffsetDateTime::now_utc();
+        let extend_lock_status: ExtendLockStatus = self
+            .extend_lock_action
+            .prepare_invoke(self.mid, self.token, unix_timestamp_ms(now), lock)
+            .invoke_async(&mut redis_conn)
+            .await
+            .map_err(YqError::ExtendLock)?;
+
+        match extend_lock_status {
+            ExtendLockStatus::Extended(_) => Ok(true),
+            ExtendLockStatus::Expired | ExtendLockStatus::Lost | ExtendLockStatus::Missing => {
+                Ok(false)
+            }
+            ExtendLockStatus::Unknown(err) => Err(YqError::ExtendLock(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "extend lock error",
+                err,
+            )))),
+        }
+    }
+}
@@ -1,21 +1,53 @@
 use crate::async_job::{AsyncJob, AsyncJobFns};
-use redis::{aio::ConnectionManager, AsyncCommands, Client, RedisResult};
+use crate::AsyncJobContext;
+use redis::{aio::ConnectionManager, Client, RedisResult};
+use std::mem;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 use yq::{
-    DequeueAction, DequeueSleep, DequeueStatus, FinishAction, Queue, SleepOnAction, YqError,
-    YqResult,
+    unix_timestamp_ms, Codec, DequeueAction, DequeueHandle, DequeueSleep, DequeueStatus,
+    ExtendLockAction, FailAction, FailStatus, FinishAction, JobLimits, Queue, ReleaseAction,
+    ReleaseStatus, RetryPolicy, SleepOnAction, YqError, YqResult, YqRunJobError,
 };
 
+const MIN_HEARTBEAT: Duration = Duration::from_millis(100);
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
 pub struct AsyncWorker<S> {
     connection_manager: ConnectionManager,
+    // The blocking sleep would hold up the commands of running jobs
+    sleep_connection: ConnectionManager,
     queue: Queue,
     dequeue_action: DequeueAction,
     finish_action: FinishAction,
+    fail_action: FailAction,
     sleep_on_action: SleepOnAction,
+    extend_lock_action: Arc<ExtendLockAction>,
+    release_action: ReleaseAction,
     async_job_fns: AsyncJobFns<S>,
+    job_limits: JobLimits,
+    shutdown: CancellationToken,
+    grace_period: Duration,
+    #[cfg(feature = "signal")]
+    shutdown_on_signal: bool,
+    concurrency: usize,
     state: S,
 }
 
+// Shared by the tasks of the jobs in flight
+struct JobRunner<S> {
+    queue: Queue,
+    finish_action: FinishAction,
+    fail_action: FailAction,
+    extend_lock_action: Arc<ExtendLockAction>,
+    release_action: ReleaseAction,
+    async_job_fns: AsyncJobFns<S>,
+    shutdown: CancellationToken,
+    grace_period: Duration,
+}
+
 impl<S> AsyncWorker<S>
 where
     S: Send + Sync + Clone + 'static,
@@ -26,53 +58,131 @@ where
             .get_tokio_connection_manager()
             .await
             .map_err(YqError::GetRedisConn)?;
+        let sleep_connection = client
+            .get_tokio_connection_manager()
+            .await
+            .map_err(YqError::GetRedisConn)?;
 
         Ok(Self {
             connection_manager,
+            sleep_connection,
             queue: queue.clone(),
             dequeue_action: DequeueAction::new(queue.clone()),
             finish_action: FinishAction::new(queue.clone()),
-            sleep_on_action: SleepOnAction::new(queue),
+            fail_action: FailAction::new(queue.clone()),
+            sleep_on_action: SleepOnAction::new(queue.clone()),
+            extend_lock_action: Arc::new(ExtendLockAction::new(queue.clone())),
+            release_action: ReleaseAction::new(queue),
             async_job_fns: AsyncJobFns::new(),
+            job_limits: JobLimits::new(),
+            shutdown: CancellationToken::new(),
+            grace_period: DEFAULT_GRACE_PERIOD,
+            #[cfg(feature = "signal")]
+            shutdown_on_signal: false,
+            concurrency: 1,
             state,
         })
     }
 
+    // Most jobs run at once as tokio tasks, 1 by default
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    // Cancelling the token stops `run`, share one token to stop several workers
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    // How long a shutdown waits for running jobs before their locks are released
+    pub fn grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    // Cancels the shutdown token on SIGTERM or SIGINT
+    #[cfg(feature = "signal")]
+    pub fn shutdown_on_signal(mut self) -> Self {
+        self.shutdown_on_signal = true;
+        self
+    }
+
     pub fn reg_job<J: AsyncJob<State = S>>(mut self) -> YqResult<Self> {
         let job_type = J::JOB_TYPE;
 
         self.async_job_fns.reg_job(
             job_type,
-            Arc::new(|mid, job_content, state| {
+            Arc::new(|ctx, job_content, state| {
                 Box::pin(async move {
-                    let job_data: J =
-                        serde_json::from_str(&job_content).map_err(|err| err.to_string())?;
-                    job_data.execute_async(mid, state).await
+                    let job_data: J = Codec::decode(&job_content)?;
+                    let output = job_data.execute_async(ctx, state).await.map_err(|error| {
+                        YqError::RunJobError(YqRunJobError::new(job_content, error))
+                    })?;
+                    serde_json::to_string(&output).map_err(YqError::SerializeResult)
                 })
             }),
+            J::RETRY_POLICY,
+            J::TIMEOUT,
         )?;
+        self.job_limits.add::<J>();
         Ok(self)
     }
 
     async fn sleep(&mut self, dequeue_sleep: DequeueSleep) {
-        let r: RedisResult<Option<String>> = self
-            .sleep_on_action
-            .prepare_invoke(dequeue_sleep)
-            .query_async(&mut self.connection_manager)
-            .await;
+        let sleep_on = self.sleep_on_action.prepare_invoke(dequeue_sleep);
+        let r: RedisResult<Option<String>> = tokio::select! {
+            r = sleep_on.query_async(&mut self.sleep_connection) => r,
+            _ = self.shutdown.cancelled() => Ok(None),
+        };
 
         if let Err(err) = r {
             tracing::error!("worker sleep ERROR: {}", err.to_string());
         }
     }
 
+    // Returns once the shutdown token is cancelled and the running jobs are over
     pub async fn run(mut self) -> YqResult<()> {
-        loop {
+        #[cfg(feature = "signal")]
+        if self.shutdown_on_signal {
+            tokio::spawn(cancel_on_signal(self.shutdown.clone()));
+        }
+
+        let runner = Arc::new(JobRunner {
+            queue: self.queue.clone(),
+            finish_action: self.finish_action.clone(),
+            fail_action: self.fail_action.clone(),
+            extend_lock_action: self.extend_lock_action.clone(),
+            release_action: self.release_action.clone(),
+            async_job_fns: mem::replace(&mut self.async_job_fns, AsyncJobFns::new()),
+            shutdown: self.shutdown.clone(),
+            grace_period: self.grace_period,
+        });
+        let mut in_flight = JoinSet::new();
+
+        while !self.shutdown.is_cancelled() {
+            // Dequeue only when a slot is free
+            while let Some(r) = in_flight.try_join_next() {
+                log_join_error(r);
+            }
+            if in_flight.len() >= self.concurrency {
+                tokio::select! {
+                    Some(r) = in_flight.join_next() => log_join_error(r),
+                    _ = self.shutdown.cancelled() => {}
+                }
+                continue;
+            }
+
             let now = time::OffsetDateTime::now_utc();
 
             let dequeue_status: RedisResult<DequeueStatus> = self
                 .dequeue_action
-                .prepare_invoke(now.unix_timestamp())
+                .prepare_invoke(unix_timestamp_ms(now), &self.job_limits)
                 .invoke_async(&mut self.connection_manager)
                 .await;
 
@@ -92,37 +202,24 @@ where
                     self.sleep(dequeue_sleep).await;
                 }
                 DequeueStatus::Handle(dequeue_handle) => {
-                    match self
-                        .async_job_fns
-                        .handle(
-                            dequeue_handle.mid,
-                            dequeue_handle.mcontent,
-                            self.state.clone(),
-                        )
-                        .await
-                    {
-                        Ok(_) => {
-                            let r: RedisResult<i64> = self
-                                .finish_action
-                                .prepare_invoke(dequeue_handle.mid)
-                                .query_async(&mut self.connection_manager)
-                                .await;
-
-                            if let Err(err) = r {
-                                tracing::error!(
-                                    "error when finish_job: {} - {}, {:?}",
-                                    &self.queue.queue_name,
-                                    dequeue_handle.mid,
-                                    err
-                                );
-                            }
-                        }
-                        Err(err) => {
-                            if let Err(err) = self.fail_job(dequeue_handle.mid, err).await {
-                                tracing::error!("{:?}", err);
-                            }
-                        }
+                    if self.shutdown.is_cancelled() {
+                        runner
+                            .release(
+                                &mut self.connection_manager,
+                                dequeue_handle.mid,
+                                dequeue_handle.token,
+                                &dequeue_handle.job_type,
+                                false,
+                            )
+                            .await;
+                        break;
                     }
+
+                    in_flight.spawn(runner.clone().run_job(
+                        self.connection_manager.clone(),
+                        dequeue_handle,
+                        self.state.clone(),
+                    ));
                 }
                 DequeueStatus::Skip(_dequeue_skip) => {
                     // skip and continue
@@ -130,31 +227,354 @@ where
                 DequeueStatus::Unknown(s) => panic!("{}", s),
             }
         }
+
+        // Each job waits at most the grace period
+        while let Some(r) = in_flight.join_next().await {
+            log_join_error(r);
+        }
+
+        tracing::info!("worker shut down: {}", &self.queue.queue_name);
+        Ok(())
     }
+}
+
+impl<S> JobRunner<S>
+where
+    S: Send + Sync + Clone + 'static,
+{
+    // Runs the job, then finishes or fails it
+    async fn run_job(
+        self: Arc<Self>,
+        mut connection_manager: ConnectionManager,
+        dequeue_handle: DequeueHandle,
+        state: S,
+    ) {
+        let retry_policy = self.async_job_fns.retry_policy(&dequeue_handle.mcontent);
+        let ctx = AsyncJobContext::new(
+            dequeue_handle.mid,
+            dequeue_handle.token,
+            dequeue_handle.attempts,
+            connection_manager.clone(),
+            self.extend_lock_action.clone(),
+        );
+        let heartbeat = tokio::spawn(heartbeat(ctx.clone(), dequeue_handle.lock));
+
+        let r = {
+            let execution = self.async_job_fns.handle(
+                ctx,
+                dequeue_handle.mcontent,
+                state,
+                dequeue_handle.timeout,
+            );
+            tokio::pin!(execution);
+            tokio::select! {
+                r = &mut execution => Some(r),
+                _ = self.shutdown.cancelled() => {
+                    tokio::time::timeout(self.grace_period, &mut execution).await.ok()
+                }
+            }
+        };
+        heartbeat.abort();
 
-    async fn fail_job(&mut self, job_id: i64, err: YqError) -> YqResult<()> {
-        match err {
-            YqError::RunJobError(run_job_error) => {
-                self.connection_manager
-                    .hset(
-                        self.queue.err_messages_key.as_str(),
-                        job_id,
-                        run_job_error.job_data,
+        let Some(r) = r else {
+            tracing::warn!(
+                "job outlived the grace period: {} - {}",
+                &self.queue.queue_name,
+                dequeue_handle.mid
+            );
+            self.release(
+                &mut connection_manager,
+                dequeue_handle.mid,
+                dequeue_handle.token,
+                &dequeue_handle.job_type,
+                true,
+            )
+            .await;
+            return;
+        };
+
+        match r {
+            Ok(result) => {
+                let now = time::OffsetDateTime::now_utc();
+                let r: RedisResult<i64> = self
+                    .finish_action
+                    .prepare_invoke(
+                        dequeue_handle.mid,
+                        dequeue_handle.token,
+                        &dequeue_handle.job_type,
+                        &result,
+                        unix_timestamp_ms(now),
                     )
-                    .await
-                    .map_err(YqError::FailJobError)?;
-                self.connection_manager
-                    .hset(self.queue.err_key.as_str(), job_id, run_job_error.error)
-                    .await
-                    .map_err(YqError::FailJobError)?;
+                    .invoke_async(&mut connection_manager)
+                    .await;
+
+                match r {
+                    Ok(-1) => tracing::warn!(
+                        "finish_job lost lock: {} - {}",
+                        &self.queue.queue_name,
+                        dequeue_handle.mid
+                    ),
+                    Ok(_) => {}
+                    Err(err) => tracing::error!(
+                        "error when finish_job: {} - {}, {:?}",
+                        &self.queue.queue_name,
+                        dequeue_handle.mid,
+                        err
+                    ),
+                }
             }
-            other => {
-                self.connection_manager
-                    .hset(self.queue.err_key.as_str(), job_id, other.to_string())
+            Err(err) => {
+                if let Err(err) = self
+                    .fail_job(
+                        &mut connection_manager,
+                        dequeue_handle.mid,
+                        dequeue_handle.token,
+                        dequeue_handle.attempts,
+                        &dequeue_handle.job_type,
+                        err,
+                        &retry_policy,
+                    )
                     .await
-                    .map_err(YqError::FailJobError)?;
+                {
+                    tracing::error!("{:?}", err);
+                }
             }
         }
+    }
+
+    // Hands the mid back right away instead of after its lock expires
+    async fn release(
+        &self,
+        connection_manager: &mut ConnectionManager,
+        job_id: i64,
+        token: i64,
+        job_type: &str,
+        started: bool,
+    ) {
+        let now = time::OffsetDateTime::now_utc();
+        let r: RedisResult<ReleaseStatus> = self
+            .release_action
+            .prepare_invoke(job_id, token, job_type, unix_timestamp_ms(now), started)
+            .invoke_async(connection_manager)
+            .await;
+
+        if let Err(err) = r {
+            tracing::error!(
+                "error when release_job: {} - {}, {:?}",
+                &self.queue.queue_name,
+                job_id,
+                err
+            );
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn fail_job(
+        &self,
+        connection_manager: &mut ConnectionManager,
+        job_id: i64,
+        token: i64,
+        attempts: i64,
+        job_type: &str,
+        err: YqError,
+        retry_policy: &RetryPolicy,
+    ) -> YqResult<()> {
+        let retry_policy = if err.is_decode_error() {
+            &RetryPolicy::NONE
+        } else {
+            retry_policy
+        };
+
+        let (error, job_data) = match err {
+            YqError::RunJobError(run_job_error) => (run_job_error.error, run_job_error.job_data),
+            other => (format!("{other:?}"), String::new()),
+        };
+
+        let now = time::OffsetDateTime::now_utc();
+        let fail_status: FailStatus = self
+            .fail_action
+            .prepare_invoke(
+                job_id,
+                token,
+                attempts,
+                job_type,
+                unix_timestamp_ms(now),
+                &error,
+                &job_data,
+                retry_policy,
+            )
+            .invoke_async(connection_manager)
+            .await
+            .map_err(YqError::FailJobError)?;
+
+        match fail_status {
+            FailStatus::Retry { attempts, run_at } => {
+                tracing::warn!(
+                    "job failed, retry at {run_at}: {} - {job_id}, attempts={attempts}, {error}",
+                    &self.queue.queue_name
+                );
+            }
+            FailStatus::Dead { attempts } => {
+                tracing::error!(
+                    "job dead: {} - {job_id}, attempts={attempts}, {error}",
+                    &self.queue.queue_name
+                );
+            }
+            FailStatus::Skip(reason) => {
+                tracing::warn!(
+                    "fail_job skipped: {} - {job_id}, {reason}",
+                    &self.queue.queue_name
+                );
+            }
+            FailStatus::Unknown(s) => {
+                tracing::error!("fail_job unknown status: {s}");
+            }
+        }
+
         Ok(())
     }
 }
+
+// A panicking handler leaves its mid locked until the lock expires
+fn log_join_error(r: Result<(), tokio::task::JoinError>) {
+    if let Err(err) = r {
+        tracing::error!("job task ERROR: {err:?}");
+    }
+}
+
+// Extends the lock every third of its duration while the handler runs, so a
+// slow handler is not handed to a second worker
+async fn heartbeat(ctx: AsyncJobContext, lock: Duration) {
+    let interval = (lock / 3).max(MIN_HEARTBEAT);
+    loop {
+        tokio::time::sleep(interval).await;
+        match ctx.extend_lock(lock).await {
+            Ok(true) => {}
+            Ok(false) => {
+                tracing::warn!("heartbeat lost lock: {}", ctx.mid());
+                return;
+            }
+            Err(err) => {
+                tracing::error!("heartbeat ERROR: {} - {err:?}", ctx.mid());
+            }
+        }
+    }
+}
+
+#[cfg(feature = "signal")]
+async fn cancel_on_signal(shutdown: CancellationToken) {
+    #[cfg(unix)]
+    let signal = async {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            r = tokio::signal::ctrl_c() => r,
+            _ = terminate.recv() => Ok(()),
+        }
+    };
+    #[cfg(not(unix))]
+    let signal = tokio::signal::ctrl_c();
+
+    tokio::select! {
+        r = signal => match r {
+            Ok(()) => {
+                tracing::info!("shutdown signal received");
+                shutdown.cancel();
+            }
+            Err(err) => tracing::error!("listen for shutdown signal ERROR: {err:?}"),
+        },
+        _ = shutdown.cancelled() => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AsyncClient;
+    use async_trait::async_trait;
+    use serde::{Deserialize, Serialize};
+    use std::time::{Instant, SystemTime, UNIX_EPOCH};
+    use tokio::sync::mpsc;
+    use yq::{Job, JobStatus, JobType};
+
+    #[derive(Serialize, Deserialize)]
+    struct Sleep {
+        ms: u64,
+    }
+
+    impl Job for Sleep {
+        const JOB_TYPE: JobType = JobType::Borrowed("sleep");
+        // Told once the job started
+        type State = mpsc::Sender<()>;
+        type Output = ();
+    }
+
+    #[async_trait]
+    impl AsyncJob for Sleep {
+        async fn execute_async(
+            self,
+            _ctx: AsyncJobContext,
+            started: Self::State,
+        ) -> Result<(), String> {
+            let _ = started.send(()).await;
+            tokio::time::sleep(Duration::from_millis(self.ms)).await;
+            Ok(())
+        }
+    }
+
+    fn redis_url() -> String {
+        std::env::var("YQ_TEST_REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".into())
+    }
+
+    fn queue() -> Queue {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        Queue::builder()
+            .prefix("yq-test")
+            .queue_name(format!("q{}", nanos.as_nanos()))
+            .build()
+            .unwrap()
+    }
+
+    // Runs the job, shuts the worker down once it started and returns its
+    // status after the worker stopped, with how long the worker took to stop
+    async fn shut_down_while_running(job: Sleep, grace_period: Duration) -> (JobStatus, Duration) {
+        let queue = queue();
+        let client = AsyncClient::new(&redis_url(), queue.clone()).await.unwrap();
+        let mid = client.schedule(&job).await.unwrap();
+
+        let (started, mut started_rx) = mpsc::channel(1);
+        let worker = AsyncWorker::new(&redis_url(), queue, started)
+            .await
+            .unwrap()
+            .reg_job::<Sleep>()
+            .unwrap()
+            .grace_period(grace_period);
+        let shutdown = worker.shutdown_token();
+        let worker = tokio::spawn(worker.run());
+
+        tokio::time::timeout(Duration::from_secs(10), started_rx.recv())
+            .await
+            .unwrap();
+        let shutdown_at = Instant::now();
+        shutdown.cancel();
+        worker.await.unwrap().unwrap();
+
+        (client.status(mid).await.unwrap(), shutdown_at.elapsed())
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a redis at YQ_TEST_REDIS_URL"]
+    async fn shutdown_waits_for_running_job() {
+        let (status, _) = shut_down_while_running(Sleep { ms: 300 }, Duration::from_secs(10)).await;
+        assert_eq!(status, JobStatus::Done);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a redis at YQ_TEST_REDIS_URL"]
+    async fn shutdown_releases_job_past_grace_period() {
+        let (status, took) =
+            shut_down_while_running(Sleep { ms: 60_000 }, Duration::from_millis(100)).await;
+        assert_eq!(status, JobStatus::Queued);
+        assert!(took < Duration::from_secs(5), "{took:?}");
+    }
+}
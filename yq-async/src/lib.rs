@@ -1,5 +1,9 @@
 mod async_client;
 mod async_job;
+mod async_job_context;
 mod async_worker;
 
-pub use {async_client::AsyncClient, async_job::AsyncJob, async_worker::AsyncWorker};
+pub use {
+    async_client::AsyncClient, async_job::AsyncJob, async_job_context::AsyncJobContext,
+    async_worker::AsyncWorker,
+};
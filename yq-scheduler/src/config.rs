@@ -0,0 +1,156 @@
+use clap::Parser;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use yq::Queue;
+
+const DEFAULT_POLL_INTERVAL_MS: u64 = 10_000;
+const DEFAULT_ERROR_BACKOFF_MS: u64 = 60_000;
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+// Every option can be given as a flag, an env var or in the toml file;
+// flags win over env vars, env vars win over the file.
+#[derive(Parser, Debug)]
+#[command(
+    name = "yq-scheduler",
+    version,
+    about = "Yet another job queue - schedule"
+)]
+struct Args {
+    #[arg(env = "YQ_REDIS_URL", help = "Redis url, e.g. redis://127.0.0.1/")]
+    redis_url: Option<String>,
+
+    #[arg(short, long, env = "YQ_CONFIG", help = "Path of a toml config file")]
+    config: Option<PathBuf>,
+
+    #[arg(long, env = "YQ_PREFIX", help = "Prefix of queues given without one")]
+    prefix: Option<String>,
+
+    #[arg(
+        short = 'q',
+        long = "queue",
+        env = "YQ_QUEUES",
+        value_delimiter = ',',
+        help = "Queue to schedule, as `name` or `prefix:name`; may be repeated"
+    )]
+    queues: Vec<String>,
+
+    #[arg(long, env = "YQ_POLL_INTERVAL_MS")]
+    poll_interval_ms: Option<u64>,
+
+    #[arg(long, env = "YQ_ERROR_BACKOFF_MS")]
+    error_backoff_ms: Option<u64>,
+
+    #[arg(
+        long,
+        env = "YQ_LOG_LEVEL",
+        help = "Level or filter, e.g. `info` or `yq=trace`"
+    )]
+    log_level: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    redis_url: Option<String>,
+    prefix: Option<String>,
+    #[serde(default)]
+    queues: Vec<String>,
+    poll_interval_ms: Option<u64>,
+    error_backoff_ms: Option<u64>,
+    log_level: Option<String>,
+}
+
+impl FileConfig {
+    fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("Could not read config {}: {err}", path.display()))?;
+        toml::from_str(&content)
+            .map_err(|err| format!("Invalid config {}: {err}", path.display()).into())
+    }
+}
+
+pub(crate) struct Config {
+    pub(crate) redis_url: String,
+    pub(crate) queues: Vec<Queue>,
+    pub(crate) poll_interval: Duration,
+    pub(crate) error_backoff: Duration,
+    pub(crate) log_level: String,
+}
+
+impl Config {
+    pub(crate) fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let args = Args::parse();
+        let file = match &args.config {
+            Some(path) => FileConfig::load(path)?,
+            None => FileConfig::default(),
+        };
+
+        let redis_url = args
+            .redis_url
+            .or(file.redis_url)
+            .ok_or("Could not get YQ_REDIS_URL from ENV, ARGS or config")?;
+
+        let prefix = args.prefix.or(file.prefix);
+        let queue_names = if args.queues.is_empty() {
+            file.queues
+        } else {
+            args.queues
+        };
+        let queues = if queue_names.is_empty() {
+            vec![build_queue(prefix.as_deref(), None)?]
+        } else {
+            queue_names
+                .iter()
+                .map(|queue_name| build_queue(prefix.as_deref(), Some(queue_name)))
+                .collect::<Result<_, _>>()?
+        };
+
+        let poll_interval_ms = args
+            .poll_interval_ms
+            .or(file.poll_interval_ms)
+            .unwrap_or(DEFAULT_POLL_INTERVAL_MS);
+        if poll_interval_ms == 0 {
+            return Err("Invalid poll_interval_ms: 0, the scheduler would never sleep".into());
+        }
+        let error_backoff_ms = args
+            .error_backoff_ms
+            .or(file.error_backoff_ms)
+            .unwrap_or(DEFAULT_ERROR_BACKOFF_MS);
+        let log_level = args
+            .log_level
+            .or(file.log_level)
+            .unwrap_or_else(|| DEFAULT_LOG_LEVEL.into());
+
+        Ok(Self {
+            redis_url,
+            queues,
+            poll_interval: Duration::from_millis(poll_interval_ms),
+            error_backoff: Duration::from_millis(error_backoff_ms),
+            log_level,
+        })
+    }
+}
+
+fn build_queue(
+    default_prefix: Option<&str>,
+    queue: Option<&str>,
+) -> Result<Queue, Box<dyn std::error::Error>> {
+    let (prefix, queue_name) = match queue.map(|queue| queue.split_once(':')) {
+        Some(Some((prefix, queue_name))) => (Some(prefix), Some(queue_name)),
+        Some(None) => (default_prefix, queue),
+        None => (default_prefix, None),
+    };
+
+    let mut builder = Queue::builder();
+    if let Some(prefix) = prefix {
+        builder = builder.prefix(prefix);
+    }
+    if let Some(queue_name) = queue_name {
+        builder = builder.queue_name(queue_name);
+    }
+
+    builder
+        .build()
+        .map_err(|err| format!("Invalid queue {}: {err:?}", queue.unwrap_or_default()).into())
+}
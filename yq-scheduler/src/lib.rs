@@ -1,61 +1,301 @@
 use redis::aio::ConnectionManager;
-use redis::Client;
-use yq::{DequeueAtAction, DequeueAtStatus, Queue, YqError, YqResult};
+use redis::{Client, FromRedisValue};
+use std::time::Duration;
+use yq::{
+    unix_timestamp_ms, DequeueAtAction, DequeueAtStatus, PeriodicAction, PeriodicClaimStatus,
+    PeriodicJob, Queue, YqError, YqResult,
+};
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_ERROR_BACKOFF: Duration = Duration::from_secs(60);
+// Least wait between polls, so an overdue mid that is not moved yet does not
+// spin the loop
+const MIN_WAIT: Duration = Duration::from_millis(10);
+// Next run of a periodic job that cannot run, 9999-12-31, until it is
+// registered again
+const PARKED_AT: i64 = 253_402_300_799_000;
 
 pub struct Scheduler {
     connection_manager: ConnectionManager,
+    queues: Vec<ScheduledQueue>,
+    poll_interval: Duration,
+    error_backoff: Duration,
+}
+
+struct ScheduledQueue {
+    queue: Queue,
     dequeue_at_action: DequeueAtAction,
+    periodic_action: PeriodicAction,
 }
 
 impl Scheduler {
-    pub async fn new(redis_url: &str) -> YqResult<Self> {
+    pub async fn new(redis_url: &str, queues: Vec<Queue>) -> YqResult<Self> {
         let client = Client::open(redis_url).map_err(YqError::CreateRedisClient)?;
         let connection_manager = client
             .get_tokio_connection_manager()
             .await
             .map_err(YqError::GetRedisConn)?;
 
-        let queue = Queue::default();
+        let queues = queues
+            .into_iter()
+            .map(|queue| ScheduledQueue {
+                dequeue_at_action: DequeueAtAction::new(queue.clone()),
+                periodic_action: PeriodicAction::new(queue.clone()),
+                queue,
+            })
+            .collect();
 
         Ok(Self {
             connection_manager,
-            dequeue_at_action: DequeueAtAction::new(queue),
+            queues,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            error_backoff: DEFAULT_ERROR_BACKOFF,
         })
     }
 
+    // Longest wait between polls, a wait is never under 10ms
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn error_backoff(mut self, error_backoff: Duration) -> Self {
+        self.error_backoff = error_backoff;
+        self
+    }
+
     pub async fn run(mut self) -> YqResult<()> {
+        for scheduled in &self.queues {
+            tracing::info!(
+                "scheduling queue {}:{}",
+                scheduled.queue.prefix,
+                scheduled.queue.queue_name
+            );
+        }
+
         loop {
             if let Err(err) = self.dequeue_loop().await {
                 tracing::error!("dequeue_at_loop ERROR: {err:?}");
-                tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+                tokio::time::sleep(self.error_backoff).await;
             }
         }
     }
 
     async fn dequeue_loop(&mut self) -> YqResult<()> {
         loop {
-            let now = time::OffsetDateTime::now_utc();
+            let now = unix_timestamp_ms(time::OffsetDateTime::now_utc());
+            let mut dequeued = 0;
+            // Earliest scheduled mid or periodic occurrence over all queues
+            let mut wake_at: Option<i64> = None;
+
+            for scheduled in &self.queues {
+                let periodic_next_at =
+                    Self::materialize_periodic(scheduled, &mut self.connection_manager, now)
+                        .await?;
+                wake_at = earliest(wake_at, periodic_next_at);
+
+                let dequeue_at_status: DequeueAtStatus = scheduled
+                    .dequeue_at_action
+                    .prepare_invoke(now)
+                    .invoke_async(&mut self.connection_manager)
+                    .await
+                    .map_err(YqError::DequeueAt)?;
+
+                match dequeue_at_status {
+                    DequeueAtStatus::Dequeued { count, next_at } => {
+                        tracing::trace!(
+                            "dequeued {count} jobs - {}:{}",
+                            scheduled.queue.prefix,
+                            scheduled.queue.queue_name
+                        );
+                        dequeued += count;
+                        wake_at = earliest(wake_at, next_at);
+                    }
+                    DequeueAtStatus::NoJob { next_at } => {
+                        wake_at = earliest(wake_at, next_at);
+                    }
+                    DequeueAtStatus::Unknown(err) => {
+                        tracing::error!(
+                            "dequeued ERROR: {}:{} - {err}",
+                            scheduled.queue.prefix,
+                            scheduled.queue.queue_name
+                        );
+                    }
+                }
+            }
+
+            if dequeued == 0 {
+                tracing::trace!("dequeued no jobs");
+            }
+
+            // Poll interval bounds the wait, a job scheduled earlier than
+            // `wake_at` meanwhile is picked up by the next poll
+            let wait = match wake_at {
+                Some(wake_at) => {
+                    let until_wake = Duration::from_millis((wake_at - now).max(0) as u64);
+                    until_wake.min(self.poll_interval)
+                }
+                None => self.poll_interval,
+            };
+            tokio::time::sleep(wait.max(MIN_WAIT)).await;
+        }
+    }
+
+    // Each due occurrence is claimed by advancing `periodic-next` with a
+    // compare-and-set, which schedules its job in the same script, so only one
+    // of several schedulers enqueues it and a failure in between cannot lose it
+    async fn materialize_periodic(
+        scheduled: &ScheduledQueue,
+        connection_manager: &mut ConnectionManager,
+        now: i64,
+    ) -> YqResult<Option<i64>> {
+        let due: Vec<(String, i64)> = scheduled
+            .periodic_action
+            .prepare_due(now)
+            .query_async(connection_manager)
+            .await
+            .map_err(YqError::Periodic)?;
 
-            let dequeue_at_status: DequeueAtStatus = self
-                .dequeue_at_action
-                .prepare_invoke(now.unix_timestamp())
-                .invoke_async(&mut self.connection_manager)
+        for (name, run_at) in due {
+            let definition: Option<redis::Value> = scheduled
+                .periodic_action
+                .prepare_get(&name)
+                .query_async(connection_manager)
                 .await
-                .map_err(YqError::DequeueAt)?;
+                .map_err(YqError::Periodic)?;
 
-            match dequeue_at_status {
-                DequeueAtStatus::Dequeued(count) => {
-                    tracing::trace!("dequeued {count} jobs");
+            // Removed meanwhile, the claim drops the stale occurrence. One that
+            // cannot run is parked rather than due again on every poll.
+            let (next_run_at, periodic) = match definition
+                .as_ref()
+                .map(PeriodicJob::from_redis_value)
+                .transpose()
+            {
+                Ok(Some(periodic)) => match periodic.recurrence.next_due(run_at, now) {
+                    Ok(next_run_at) => (next_run_at, Some(periodic)),
+                    Err(err) => {
+                        tracing::error!("periodic ERROR, parked: {name} - {err:?}");
+                        (PARKED_AT, None)
+                    }
+                },
+                Ok(None) => (now, None),
+                Err(err) => {
+                    tracing::error!("periodic ERROR, parked: {name} - {err:?}");
+                    (PARKED_AT, None)
                 }
-                DequeueAtStatus::NoJob => {
-                    tracing::trace!("dequeued no jobs");
-                    tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+            };
+
+            let claim_status: PeriodicClaimStatus = scheduled
+                .periodic_action
+                .prepare_claim(
+                    &name,
+                    run_at,
+                    next_run_at,
+                    periodic.as_ref().map(PeriodicJob::job),
+                )
+                .invoke_async(connection_manager)
+                .await
+                .map_err(YqError::Periodic)?;
+
+            match claim_status {
+                PeriodicClaimStatus::Added(mid) | PeriodicClaimStatus::Exists(mid) => {
+                    tracing::debug!(
+                        "periodic {name} at {run_at} - mid {mid} - next at {next_run_at} - {}:{}",
+                        scheduled.queue.prefix,
+                        scheduled.queue.queue_name
+                    );
                 }
-                DequeueAtStatus::Unknown(err) => {
-                    tracing::error!("dequeued ERROR: {err}");
-                    tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+                PeriodicClaimStatus::Advanced | PeriodicClaimStatus::Lost => {}
+                PeriodicClaimStatus::Unknown(err) => {
+                    tracing::error!(
+                        "periodic ERROR: {name} - {}:{} - {err}",
+                        scheduled.queue.prefix,
+                        scheduled.queue.queue_name
+                    );
                 }
             }
         }
+
+        let next: Vec<(String, i64)> = scheduled
+            .periodic_action
+            .prepare_next()
+            .query_async(connection_manager)
+            .await
+            .map_err(YqError::Periodic)?;
+
+        Ok(next.into_iter().next().map(|(_name, next_at)| next_at))
+    }
+}
+
+fn earliest(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use yq::{Job, JobType, Recurrence};
+
+    // 2000-01-01
+    const Y2K: i64 = 946_684_800_000;
+
+    #[derive(Serialize, Deserialize)]
+    struct Tick;
+
+    impl Job for Tick {
+        const JOB_TYPE: JobType = JobType::Borrowed("tick");
+        type State = ();
+        type Output = ();
+    }
+
+    async fn scheduler() -> Scheduler {
+        let redis_url =
+            std::env::var("YQ_TEST_REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".into());
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let queue = Queue::builder()
+            .prefix("yq-test")
+            .queue_name(format!("q{}", nanos.as_nanos()))
+            .build()
+            .unwrap();
+        Scheduler::new(&redis_url, vec![queue]).await.unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a redis at YQ_TEST_REDIS_URL"]
+    async fn parks_periodic_job_without_next_run() {
+        let mut scheduler = scheduler().await;
+        let scheduled = &scheduler.queues[0];
+
+        // Registered just before its only occurrence
+        let mut periodic =
+            PeriodicJob::new("y2k", Recurrence::Every(Duration::from_secs(60)), &Tick).unwrap();
+        periodic.recurrence = Recurrence::Cron("0 0 0 1 1 * 2000".into());
+        scheduled
+            .periodic_action
+            .prepare_register(&periodic, Y2K - 1000)
+            .unwrap()
+            .query_async::<_, ()>(&mut scheduler.connection_manager)
+            .await
+            .unwrap();
+
+        let now = unix_timestamp_ms(time::OffsetDateTime::now_utc());
+        let next_at =
+            Scheduler::materialize_periodic(scheduled, &mut scheduler.connection_manager, now)
+                .await
+                .unwrap();
+        assert_eq!(next_at, Some(PARKED_AT));
+
+        let due: Vec<(String, i64)> = scheduled
+            .periodic_action
+            .prepare_due(now)
+            .query_async(&mut scheduler.connection_manager)
+            .await
+            .unwrap();
+        assert!(due.is_empty(), "{due:?}");
     }
 }
@@ -1,31 +1,25 @@
+mod config;
+
+use config::Config;
 use yq_scheduler::Scheduler;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    init_tracing();
+    let config = Config::load()?;
+    init_tracing(&config.log_level);
 
-    let redis_url = try_get_redis_url()?;
-    let scheduler = Scheduler::new(&redis_url).await?;
+    let scheduler = Scheduler::new(&config.redis_url, config.queues)
+        .await?
+        .poll_interval(config.poll_interval)
+        .error_backoff(config.error_backoff);
     scheduler.run().await?;
 
     Ok(())
 }
 
-fn try_get_redis_url() -> Result<String, Box<dyn std::error::Error>> {
-    if let Ok(redis_url) = std::env::var("YQ_REDIS_URL") {
-        return Ok(redis_url);
-    }
-
-    let mut args = std::env::args();
-    args.next();
-
-    args.next()
-        .ok_or_else(|| "Could not get YQ_REDIS_URL from ENV or ARGS".into())
-}
-
-fn init_tracing() {
+fn init_tracing(log_level: &str) {
     let subscriber = tracing_subscriber::FmtSubscriber::builder()
-        .with_max_level(tracing::Level::INFO)
+        .with_env_filter(log_level)
         .finish();
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 }
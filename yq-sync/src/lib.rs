@@ -1,5 +1,11 @@
+mod shutdown;
 mod sync_client;
 mod sync_job;
+mod sync_job_context;
 mod sync_worker;
+mod worker_connection;
 
-pub use {sync_client::SyncClient, sync_job::SyncJob, sync_worker::SyncWorker};
+pub use {
+    shutdown::ShutdownHandle, sync_client::SyncClient, sync_job::SyncJob,
+    sync_job_context::SyncJobContext, sync_worker::SyncWorker,
+};
@@ -0,0 +1,32 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Stops every `SyncWorker` sharing the handle. A sleeping worker notices once
+// its dequeue sleep ends.
+#[derive(Clone, Debug, Default)]
+pub struct ShutdownHandle(Arc<AtomicBool>);
+
+impl ShutdownHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn shutdown(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    // Shuts down on SIGTERM or SIGINT
+    #[cfg(feature = "signal")]
+    pub fn shutdown_on_signal(&self) -> yq::YqResult<()> {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+
+        for signal in [SIGTERM, SIGINT] {
+            signal_hook::flag::register(signal, self.0.clone()).map_err(yq::YqError::Signal)?;
+        }
+        Ok(())
+    }
+}
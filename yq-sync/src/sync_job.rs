@@ -1,39 +1,162 @@
+use crate::SyncJobContext;
+use std::any::Any;
 use std::collections::HashMap;
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::Arc;
-use yq::{decode_job, Job, JobType, YqError, YqResult, YqRunJobError};
+use std::thread;
+use std::time::{Duration, Instant};
+use yq::{decode_job, Job, JobType, RetryPolicy, YqError, YqResult};
 
 pub trait SyncJob: Job {
-    fn execute(self, mid: i64, state: Self::State) -> Result<(), String>;
+    fn execute(self, ctx: SyncJobContext, state: Self::State) -> Result<Self::Output, String>;
 }
 
-type SyncJobFn<S> = Arc<dyn Fn(i64, String, S) -> Result<(), String>>;
+// Returns the serialized `Job::Output`
+type SyncJobFn<S> = Arc<dyn Fn(SyncJobContext, String, S) -> YqResult<String> + Send + Sync>;
 
-pub(crate) struct SyncJobFns<S>(HashMap<JobType, SyncJobFn<S>>);
+struct SyncJobEntry<S> {
+    job_fn: SyncJobFn<S>,
+    retry_policy: RetryPolicy,
+    timeout: Option<Duration>,
+}
+
+pub(crate) struct SyncJobFns<S>(HashMap<JobType, SyncJobEntry<S>>);
 
-impl<S> SyncJobFns<S> {
+impl<S: Send + 'static> SyncJobFns<S> {
     pub(crate) fn new() -> SyncJobFns<S> {
         SyncJobFns::<S>(HashMap::default())
     }
 
-    pub(crate) fn reg_job(&mut self, job_type: JobType, job_fn: SyncJobFn<S>) -> YqResult<()> {
-        if self.0.insert(job_type.clone(), job_fn).is_some() {
+    pub(crate) fn reg_job(
+        &mut self,
+        job_type: JobType,
+        job_fn: SyncJobFn<S>,
+        retry_policy: RetryPolicy,
+        timeout: Option<Duration>,
+    ) -> YqResult<()> {
+        let entry = SyncJobEntry {
+            job_fn,
+            retry_policy,
+            timeout,
+        };
+        if self.0.insert(job_type.clone(), entry).is_some() {
             Err(YqError::DupJobType(job_type))
         } else {
             Ok(())
         }
     }
 
-    pub(crate) fn handle(&self, mid: i64, mcontent: String, state: S) -> YqResult<()> {
+    // Starts the job on `job_thread`. `timeout` overrides the `Job::TIMEOUT` of
+    // the job type.
+    pub(crate) fn run<'a>(
+        &self,
+        job_thread: &'a JobThread,
+        ctx: SyncJobContext,
+        mcontent: String,
+        state: S,
+        timeout: Option<Duration>,
+    ) -> YqResult<RunningJob<'a>> {
         let (job_type, job_data) = decode_job(&mcontent)?;
 
-        let job_fn = match self.0.get(job_type) {
-            Some(job_fn) => job_fn,
+        let entry = match self.0.get(job_type) {
+            Some(entry) => entry,
             None => {
                 return Err(YqError::JobTypeMissing(JobType::from(job_type.to_string())));
             }
         };
 
-        job_fn(mid, job_data.to_string(), state)
-            .map_err(|error| YqError::RunJobError(YqRunJobError::new(job_data.to_string(), error)))
+        let job_fn = entry.job_fn.clone();
+        let job_data = job_data.to_string();
+        // The thread outlives its jobs, the job fn catches their panics
+        let _ = job_thread
+            .jobs
+            .send(Box::new(move || job_fn(ctx, job_data, state)));
+
+        Ok(RunningJob {
+            result: &job_thread.results,
+            timeout: timeout
+                .or(entry.timeout)
+                .map(|timeout| (Instant::now() + timeout, timeout)),
+        })
     }
+
+    pub(crate) fn retry_policy(&self, mcontent: &str) -> RetryPolicy {
+        decode_job(mcontent)
+            .ok()
+            .and_then(|(job_type, _)| self.0.get(job_type))
+            .map(|entry| entry.retry_policy)
+            .unwrap_or_default()
+    }
+}
+
+type JobTask = Box<dyn FnOnce() -> YqResult<String> + Send>;
+
+// Runs the jobs of a worker thread one at a time, so the worker can stop
+// waiting for one past its timeout or the grace period. A thread cannot be
+// stopped, such a job is left to its thread, its result discarded, and the
+// next job gets a new thread.
+pub(crate) struct JobThread {
+    jobs: mpsc::Sender<JobTask>,
+    results: mpsc::Receiver<YqResult<String>>,
+}
+
+impl JobThread {
+    pub(crate) fn spawn() -> Self {
+        let (jobs, job_rx) = mpsc::channel::<JobTask>();
+        let (results_tx, results) = mpsc::channel();
+        thread::spawn(move || {
+            for job in job_rx {
+                // The receiver is gone once the worker gave up on the job
+                if results_tx.send(job()).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self { jobs, results }
+    }
+}
+
+pub(crate) struct RunningJob<'a> {
+    result: &'a mpsc::Receiver<YqResult<String>>,
+    // Deadline and timeout, if any
+    timeout: Option<(Instant, Duration)>,
+}
+
+impl RunningJob<'_> {
+    // `None` while the job is still running at `until`
+    pub(crate) fn wait_until(&self, until: Instant) -> Option<YqResult<String>> {
+        let deadline = match self.timeout {
+            Some((timeout_at, _)) => until.min(timeout_at),
+            None => until,
+        };
+
+        match self
+            .result
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            Ok(result) => Some(result),
+            Err(RecvTimeoutError::Timeout) => match self.timeout {
+                Some((timeout_at, timeout)) if Instant::now() >= timeout_at => {
+                    Some(Err(YqError::JobTimeout(timeout)))
+                }
+                _ => None,
+            },
+            Err(RecvTimeoutError::Disconnected) => {
+                unreachable!("job thread exited without a result")
+            }
+        }
+    }
+}
+
+// Error of the job of a panicking handler
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let message = match payload.downcast::<&str>() {
+        Ok(message) => message.to_string(),
+        Err(payload) => payload
+            .downcast::<String>()
+            .map(|message| *message)
+            .unwrap_or_default(),
+    };
+    format!("panicked: {message}")
 }
@@ -0,0 +1,68 @@
+use crate::worker_connection::WorkerConnection;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use yq::{unix_timestamp_ms, ExtendLockAction, ExtendLockStatus, YqError, YqResult};
+
+#[derive(Clone)]
+pub struct SyncJobContext {
+    mid: i64,
+    token: i64,
+    attempts: i64,
+    // Of the worker thread, shared with its heartbeat
+    connection: Arc<Mutex<WorkerConnection>>,
+    extend_lock_action: Arc<ExtendLockAction>,
+}
+
+impl SyncJobContext {
+    pub(crate) fn new(
+        mid: i64,
+        token: i64,
+        attempts: i64,
+        connection: Arc<Mutex<WorkerConnection>>,
+        extend_lock_action: Arc<ExtendLockAction>,
+    ) -> Self {
+        Self {
+            mid,
+            token,
+            attempts,
+            connection,
+            extend_lock_action,
+        }
+    }
+
+    pub fn mid(&self) -> i64 {
+        self.mid
+    }
+
+    // 1 on the first run
+    pub fn attempts(&self) -> i64 {
+        self.attempts
+    }
+
+    // Keep the mid locked for at least `lock` from now. False once the lock
+    // expired or was lost, the job may then be running on another worker.
+    pub fn extend_lock(&self, lock: Duration) -> YqResult<bool> {
+        let mut redis_conn = self
+            .connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let now = time::OffsetDateTime::now_utc();
+        let extend_lock_status: ExtendLockStatus = self
+            .extend_lock_action
+            .prepare_invoke(self.mid, self.token, unix_timestamp_ms(now), lock)
+            .invoke(&mut *redis_conn)
+            .map_err(YqError::ExtendLock)?;
+
+        match extend_lock_status {
+            ExtendLockStatus::Extended(_) => Ok(true),
+            ExtendLockStatus::Expired | ExtendLockStatus::Lost | ExtendLockStatus::Missing => {
+                Ok(false)
+            }
+            ExtendLockStatus::Unknown(err) => Err(YqError::ExtendLock(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "extend lock error",
+                err,
+            )))),
+        }
+    }
+}
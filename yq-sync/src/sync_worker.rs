@@ -1,18 +1,39 @@
-use crate::sync_job::{SyncJob, SyncJobFns};
-use redis::{Client, Commands, RedisResult};
-use std::sync::Arc;
+use crate::sync_job::{panic_message, JobThread, RunningJob, SyncJob, SyncJobFns};
+use crate::worker_connection::WorkerConnection;
+use crate::{ShutdownHandle, SyncJobContext};
+use redis::{Client, RedisResult};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use yq::{
-    DequeueAction, DequeueSleep, DequeueStatus, FinishAction, Queue, SleepOnAction, YqError,
-    YqResult,
+    unix_timestamp_ms, Codec, DequeueAction, DequeueSleep, DequeueStatus, ExtendLockAction,
+    FailAction, FailStatus, FinishAction, JobLimits, Queue, ReleaseAction, ReleaseStatus,
+    RetryPolicy, SleepOnAction, YqError, YqResult, YqRunJobError,
 };
 
+const MIN_HEARTBEAT: Duration = Duration::from_millis(100);
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+// How often a running job checks for a shutdown
+const SHUTDOWN_POLL: Duration = Duration::from_millis(100);
+// How often a sleeping thread checks for a shutdown
+const SHUTDOWN_POLL_SLEEP_SECS: usize = 1;
+
 pub struct SyncWorker<S> {
     client: Client,
     queue: Queue,
     dequeue_action: DequeueAction,
     finish_action: FinishAction,
+    fail_action: FailAction,
     sleep_on_action: SleepOnAction,
+    extend_lock_action: Arc<ExtendLockAction>,
+    release_action: ReleaseAction,
     sync_job_fns: SyncJobFns<S>,
+    job_limits: JobLimits,
+    shutdown: ShutdownHandle,
+    grace_period: Duration,
+    threads: usize,
     state: S,
 }
 
@@ -28,51 +49,136 @@ where
             queue: queue.clone(),
             dequeue_action: DequeueAction::new(queue.clone()),
             finish_action: FinishAction::new(queue.clone()),
-            sleep_on_action: SleepOnAction::new(queue),
+            fail_action: FailAction::new(queue.clone()),
+            sleep_on_action: SleepOnAction::new(queue.clone()),
+            extend_lock_action: Arc::new(ExtendLockAction::new(queue.clone())),
+            release_action: ReleaseAction::new(queue),
             sync_job_fns: SyncJobFns::new(),
+            job_limits: JobLimits::new(),
+            shutdown: ShutdownHandle::new(),
+            grace_period: DEFAULT_GRACE_PERIOD,
+            threads: 1,
             state,
         })
     }
 
+    // OS threads running jobs, each with its own connection, 1 by default
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // Share one handle to stop several workers
+    pub fn with_shutdown(mut self, shutdown: ShutdownHandle) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    // How long a shutdown waits for running jobs, a job still running is then
+    // left to its lock
+    pub fn grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    // Shuts the worker down on SIGTERM or SIGINT
+    #[cfg(feature = "signal")]
+    pub fn shutdown_on_signal(self) -> YqResult<Self> {
+        self.shutdown.shutdown_on_signal()?;
+        Ok(self)
+    }
+
     pub fn reg_job<J: SyncJob<State = S>>(mut self) -> YqResult<Self> {
         let job_type = J::JOB_TYPE;
 
         self.sync_job_fns.reg_job(
             job_type,
-            Arc::new(|mid, job_content, state| {
-                let job_data: J =
-                    serde_json::from_str(&job_content).map_err(|err| err.to_string())?;
-                job_data.execute(mid, state)
+            Arc::new(|ctx, job_content, state| {
+                // A panicking handler fails its job, not the worker
+                panic::catch_unwind(AssertUnwindSafe(|| {
+                    let job_data: J = Codec::decode(&job_content)?;
+                    let output = job_data.execute(ctx, state).map_err(|error| {
+                        YqError::RunJobError(YqRunJobError::new(job_content.clone(), error))
+                    })?;
+                    serde_json::to_string(&output).map_err(YqError::SerializeResult)
+                }))
+                .unwrap_or_else(|payload| {
+                    Err(YqError::RunJobError(YqRunJobError::new(
+                        job_content.clone(),
+                        panic_message(payload),
+                    )))
+                })
             }),
+            J::RETRY_POLICY,
+            J::TIMEOUT,
         )?;
+        self.job_limits.add::<J>();
         Ok(self)
     }
 
-    fn sleep(&mut self, dequeue_sleep: DequeueSleep) {
-        let r: RedisResult<Option<String>> = self
-            .sleep_on_action
-            .prepare_invoke(dequeue_sleep)
-            .query(&mut self.client);
+    // Sleeps in parts, the blocking pop cannot be interrupted by a shutdown
+    fn sleep(&self, connection: &mut WorkerConnection, dequeue_sleep: DequeueSleep) {
+        let mut sleep_time = self.sleep_on_action.sleep_time(&dequeue_sleep);
+        while sleep_time > 0 && !self.shutdown.is_shutdown() {
+            let secs = sleep_time.min(SHUTDOWN_POLL_SLEEP_SECS);
+            let r: RedisResult<Option<String>> = self
+                .sleep_on_action
+                .prepare_invoke_for(&dequeue_sleep, secs)
+                .query(connection);
 
-        if let Err(err) = r {
-            tracing::error!("worker sleep ERROR: {}", err.to_string());
+            match r {
+                Ok(Some(_)) => return, // Woken by new mids
+                Ok(None) => sleep_time -= secs,
+                Err(err) => {
+                    tracing::error!("worker sleep ERROR: {}", err.to_string());
+                    return;
+                }
+            }
         }
     }
 
-    pub fn run(mut self) -> YqResult<()> {
-        loop {
+    // Returns once shut down and the running jobs are over
+    pub fn run(self) -> YqResult<()> {
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| {
+                    let _guard = ShutdownOnPanic(&self.shutdown);
+                    let (beats, beat_rx) = mpsc::channel();
+                    scope.spawn(move || heartbeat(beat_rx));
+                    let heartbeat = Heartbeat {
+                        beats,
+                        connection: Arc::new(Mutex::new(WorkerConnection::new(
+                            self.client.clone(),
+                        ))),
+                    };
+                    self.run_thread(&mut WorkerConnection::new(self.client.clone()), &heartbeat);
+                });
+            }
+        });
+
+        tracing::info!("worker shut down: {}", &self.queue.queue_name);
+        Ok(())
+    }
+
+    fn run_thread(&self, connection: &mut WorkerConnection, heartbeat: &Heartbeat) {
+        let mut job_thread: Option<JobThread> = None;
+        while !self.shutdown.is_shutdown() {
             let now = time::OffsetDateTime::now_utc();
 
             let dequeue_status: RedisResult<DequeueStatus> = self
                 .dequeue_action
-                .prepare_invoke(now.unix_timestamp())
-                .invoke(&mut self.client);
+                .prepare_invoke(unix_timestamp_ms(now), &self.job_limits)
+                .invoke(connection);
 
             let dequeue_status = match dequeue_status {
                 Ok(dequeue_status) => dequeue_status,
                 Err(err) => {
                     tracing::error!("dequeue_job ERROR: {err:?}");
-                    self.sleep(DequeueSleep::default());
+                    self.sleep(connection, DequeueSleep::default());
                     continue;
                 }
             };
@@ -80,31 +186,98 @@ where
 
             match dequeue_status {
                 DequeueStatus::Sleep(dequeue_sleep) => {
-                    self.sleep(dequeue_sleep);
+                    self.sleep(connection, dequeue_sleep);
                 }
                 DequeueStatus::Handle(dequeue_handle) => {
-                    match self.sync_job_fns.handle(
+                    if self.shutdown.is_shutdown() {
+                        self.release(
+                            connection,
+                            dequeue_handle.mid,
+                            dequeue_handle.token,
+                            &dequeue_handle.job_type,
+                            false,
+                        );
+                        break;
+                    }
+
+                    let retry_policy = self.sync_job_fns.retry_policy(&dequeue_handle.mcontent);
+                    let ctx = SyncJobContext::new(
                         dequeue_handle.mid,
+                        dequeue_handle.token,
+                        dequeue_handle.attempts,
+                        heartbeat.connection.clone(),
+                        self.extend_lock_action.clone(),
+                    );
+                    heartbeat.start(ctx.clone(), dequeue_handle.lock);
+
+                    let r = match self.sync_job_fns.run(
+                        job_thread.get_or_insert_with(JobThread::spawn),
+                        ctx,
                         dequeue_handle.mcontent,
                         self.state.clone(),
+                        dequeue_handle.timeout,
                     ) {
-                        Ok(_) => {
+                        Ok(running_job) => self.wait(&running_job),
+                        Err(err) => Some(Err(err)),
+                    };
+                    heartbeat.stop();
+
+                    // Failed and unlocked, the handler past its timeout is left to
+                    // its thread and may still run when the job is retried
+                    if let Some(Err(YqError::JobTimeout(_))) = &r {
+                        job_thread = None;
+                    }
+
+                    // Still running, so the mid is left locked until the lock
+                    // expires rather than handed to another worker
+                    let Some(r) = r else {
+                        tracing::warn!(
+                            "job outlived the grace period: {} - {}",
+                            &self.queue.queue_name,
+                            dequeue_handle.mid
+                        );
+                        break;
+                    };
+
+                    match r {
+                        Ok(result) => {
+                            let now = time::OffsetDateTime::now_utc();
                             let r: RedisResult<i64> = self
                                 .finish_action
-                                .prepare_invoke(dequeue_handle.mid)
-                                .query(&mut self.client);
+                                .prepare_invoke(
+                                    dequeue_handle.mid,
+                                    dequeue_handle.token,
+                                    &dequeue_handle.job_type,
+                                    &result,
+                                    unix_timestamp_ms(now),
+                                )
+                                .invoke(connection);
 
-                            if let Err(err) = r {
-                                tracing::error!(
+                            match r {
+                                Ok(-1) => tracing::warn!(
+                                    "finish_job lost lock: {} - {}",
+                                    &self.queue.queue_name,
+                                    dequeue_handle.mid
+                                ),
+                                Ok(_) => {}
+                                Err(err) => tracing::error!(
                                     "error when finish_job: {} - {}, {:?}",
                                     &self.queue.queue_name,
                                     dequeue_handle.mid,
                                     err
-                                );
+                                ),
                             }
                         }
                         Err(err) => {
-                            if let Err(err) = self.fail_job(dequeue_handle.mid, err) {
+                            if let Err(err) = self.fail_job(
+                                connection,
+                                dequeue_handle.mid,
+                                dequeue_handle.token,
+                                dequeue_handle.attempts,
+                                &dequeue_handle.job_type,
+                                err,
+                                &retry_policy,
+                            ) {
                                 tracing::error!("{:?}", err);
                             }
                         }
@@ -118,28 +291,289 @@ where
         }
     }
 
-    fn fail_job(&mut self, job_id: i64, err: YqError) -> YqResult<()> {
-        match err {
-            YqError::RunJobError(run_job_error) => {
-                self.client
-                    .hset(
-                        self.queue.err_messages_key.as_str(),
-                        job_id,
-                        run_job_error.job_data,
-                    )
-                    .map_err(YqError::FailJobError)?;
-
-                self.client
-                    .hset(self.queue.err_key.as_str(), job_id, run_job_error.error)
-                    .map_err(YqError::FailJobError)?;
+    // `None` when the job outlived the grace period of a shutdown
+    fn wait(&self, running_job: &RunningJob) -> Option<YqResult<String>> {
+        loop {
+            if self.shutdown.is_shutdown() {
+                return running_job.wait_until(Instant::now() + self.grace_period);
             }
-            other => {
-                self.client
-                    .hset(self.queue.err_key.as_str(), job_id, other.to_string())
-                    .map_err(YqError::FailJobError)?;
+            if let Some(r) = running_job.wait_until(Instant::now() + SHUTDOWN_POLL) {
+                return Some(r);
+            }
+        }
+    }
+
+    // Hands the mid back right away instead of after its lock expires
+    fn release(
+        &self,
+        connection: &mut WorkerConnection,
+        job_id: i64,
+        token: i64,
+        job_type: &str,
+        started: bool,
+    ) {
+        let now = time::OffsetDateTime::now_utc();
+        let r: RedisResult<ReleaseStatus> = self
+            .release_action
+            .prepare_invoke(job_id, token, job_type, unix_timestamp_ms(now), started)
+            .invoke(connection);
+
+        if let Err(err) = r {
+            tracing::error!(
+                "error when release_job: {} - {}, {:?}",
+                &self.queue.queue_name,
+                job_id,
+                err
+            );
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn fail_job(
+        &self,
+        connection: &mut WorkerConnection,
+        job_id: i64,
+        token: i64,
+        attempts: i64,
+        job_type: &str,
+        err: YqError,
+        retry_policy: &RetryPolicy,
+    ) -> YqResult<()> {
+        let retry_policy = if err.is_decode_error() {
+            &RetryPolicy::NONE
+        } else {
+            retry_policy
+        };
+
+        let (error, job_data) = match err {
+            YqError::RunJobError(run_job_error) => (run_job_error.error, run_job_error.job_data),
+            other => (format!("{other:?}"), String::new()),
+        };
+
+        let now = time::OffsetDateTime::now_utc();
+        let fail_status: FailStatus = self
+            .fail_action
+            .prepare_invoke(
+                job_id,
+                token,
+                attempts,
+                job_type,
+                unix_timestamp_ms(now),
+                &error,
+                &job_data,
+                retry_policy,
+            )
+            .invoke(connection)
+            .map_err(YqError::FailJobError)?;
+
+        match fail_status {
+            FailStatus::Retry { attempts, run_at } => {
+                tracing::warn!(
+                    "job failed, retry at {run_at}: {} - {job_id}, attempts={attempts}, {error}",
+                    &self.queue.queue_name
+                );
+            }
+            FailStatus::Dead { attempts } => {
+                tracing::error!(
+                    "job dead: {} - {job_id}, attempts={attempts}, {error}",
+                    &self.queue.queue_name
+                );
+            }
+            FailStatus::Skip(reason) => {
+                tracing::warn!(
+                    "fail_job skipped: {} - {job_id}, {reason}",
+                    &self.queue.queue_name
+                );
+            }
+            FailStatus::Unknown(s) => {
+                tracing::error!("fail_job unknown status: {s}");
             }
         }
 
         Ok(())
     }
 }
+
+// A panicking worker thread stops the others, `thread::scope` then rethrows the
+// panic. Handler panics fail their job instead.
+struct ShutdownOnPanic<'a>(&'a ShutdownHandle);
+
+impl Drop for ShutdownOnPanic<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.shutdown();
+        }
+    }
+}
+
+// The heartbeat thread and connection of a worker thread, kept for all its jobs
+struct Heartbeat {
+    beats: mpsc::Sender<Option<(SyncJobContext, Duration)>>,
+    connection: Arc<Mutex<WorkerConnection>>,
+}
+
+impl Heartbeat {
+    fn start(&self, ctx: SyncJobContext, lock: Duration) {
+        let _ = self.beats.send(Some((ctx, lock)));
+    }
+
+    fn stop(&self) {
+        let _ = self.beats.send(None);
+    }
+}
+
+// Extends the lock of the started job every third of its duration until it is
+// stopped, so a slow handler is not handed to a second worker. Returns once the
+// worker thread is gone.
+fn heartbeat(beats: mpsc::Receiver<Option<(SyncJobContext, Duration)>>) {
+    let mut job: Option<(SyncJobContext, Duration)> = None;
+    loop {
+        let beat = match &job {
+            Some((_, lock)) => beats.recv_timeout((*lock / 3).max(MIN_HEARTBEAT)),
+            None => beats.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match beat {
+            Ok(next) => job = next,
+            Err(RecvTimeoutError::Timeout) => {
+                let Some((ctx, lock)) = job.take() else {
+                    continue;
+                };
+                match ctx.extend_lock(lock) {
+                    Ok(true) => job = Some((ctx, lock)),
+                    Ok(false) => tracing::warn!("heartbeat lost lock: {}", ctx.mid()),
+                    Err(err) => {
+                        tracing::error!("heartbeat ERROR: {} - {err:?}", ctx.mid());
+                        job = Some((ctx, lock));
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SyncClient;
+    use serde::{Deserialize, Serialize};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use yq::{Job, JobStatus, JobType};
+
+    #[derive(Serialize, Deserialize)]
+    struct Panic;
+
+    impl Job for Panic {
+        const JOB_TYPE: JobType = JobType::Borrowed("panic");
+        type State = mpsc::SyncSender<()>;
+        type Output = ();
+        const RETRY_POLICY: RetryPolicy = RetryPolicy::NONE;
+    }
+
+    impl SyncJob for Panic {
+        fn execute(self, _ctx: SyncJobContext, _state: Self::State) -> Result<(), String> {
+            panic!("handler panicked");
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Sleep {
+        ms: u64,
+    }
+
+    impl Job for Sleep {
+        const JOB_TYPE: JobType = JobType::Borrowed("sleep");
+        // Told once the job started
+        type State = mpsc::SyncSender<()>;
+        type Output = ();
+    }
+
+    impl SyncJob for Sleep {
+        fn execute(self, _ctx: SyncJobContext, started: Self::State) -> Result<(), String> {
+            let _ = started.send(());
+            thread::sleep(Duration::from_millis(self.ms));
+            Ok(())
+        }
+    }
+
+    fn redis_url() -> String {
+        std::env::var("YQ_TEST_REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".into())
+    }
+
+    fn queue() -> Queue {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        Queue::builder()
+            .prefix("yq-test")
+            .queue_name(format!("q{}", nanos.as_nanos()))
+            .build()
+            .unwrap()
+    }
+
+    // Runs the job, shuts the worker down once it started and returns its
+    // status after the worker stopped, with how long the worker took to stop
+    fn shut_down_while_running(job: Sleep, grace_period: Duration) -> (JobStatus, Duration) {
+        let queue = queue();
+        let client = SyncClient::new(&redis_url(), queue.clone()).unwrap();
+        let mid = client.schedule(&job).unwrap();
+
+        let (started, started_rx) = mpsc::sync_channel(1);
+        let worker = SyncWorker::new(&redis_url(), queue, started)
+            .unwrap()
+            .reg_job::<Sleep>()
+            .unwrap()
+            .grace_period(grace_period);
+        let shutdown = worker.shutdown_handle();
+        let worker = thread::spawn(move || worker.run());
+
+        started_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        let shutdown_at = Instant::now();
+        shutdown.shutdown();
+        worker.join().unwrap().unwrap();
+
+        (client.status(mid).unwrap(), shutdown_at.elapsed())
+    }
+
+    #[test]
+    #[ignore = "needs a redis at YQ_TEST_REDIS_URL"]
+    fn panicking_job_fails_without_stopping_the_worker() {
+        let queue = queue();
+        let client = SyncClient::new(&redis_url(), queue.clone()).unwrap();
+        let panicked = client.schedule(&Panic).unwrap();
+        let slept = client.schedule(&Sleep { ms: 0 }).unwrap();
+
+        let (started, started_rx) = mpsc::sync_channel(1);
+        let worker = SyncWorker::new(&redis_url(), queue, started)
+            .unwrap()
+            .reg_job::<Panic>()
+            .unwrap()
+            .reg_job::<Sleep>()
+            .unwrap();
+        let shutdown = worker.shutdown_handle();
+        let worker = thread::spawn(move || worker.run());
+
+        // Dequeued after the panicking one
+        started_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        shutdown.shutdown();
+        worker.join().unwrap().unwrap();
+
+        assert_eq!(client.status(panicked).unwrap(), JobStatus::Dead);
+        assert_eq!(client.status(slept).unwrap(), JobStatus::Done);
+    }
+
+    #[test]
+    #[ignore = "needs a redis at YQ_TEST_REDIS_URL"]
+    fn shutdown_waits_for_running_job() {
+        let (status, _) = shut_down_while_running(Sleep { ms: 300 }, Duration::from_secs(10));
+        assert_eq!(status, JobStatus::Done);
+    }
+
+    #[test]
+    #[ignore = "needs a redis at YQ_TEST_REDIS_URL"]
+    fn shutdown_leaves_job_past_grace_period_to_its_lock() {
+        let (status, took) =
+            shut_down_while_running(Sleep { ms: 60_000 }, Duration::from_millis(100));
+        assert!(matches!(status, JobStatus::Locked(_)), "{status:?}");
+        assert!(took < Duration::from_secs(5), "{took:?}");
+    }
+}
@@ -0,0 +1,53 @@
+use redis::{Client, Connection, ConnectionLike, RedisResult, Value};
+
+// The connection of one worker thread, reopened once it broke
+pub(crate) struct WorkerConnection {
+    client: Client,
+    connection: Option<Connection>,
+}
+
+impl WorkerConnection {
+    pub(crate) fn new(client: Client) -> Self {
+        Self {
+            client,
+            connection: None,
+        }
+    }
+
+    fn connection(&mut self) -> RedisResult<&mut Connection> {
+        if let Some(connection) = self.connection.take().filter(Connection::is_open) {
+            return Ok(self.connection.insert(connection));
+        }
+        let connection = self.client.get_connection()?;
+        Ok(self.connection.insert(connection))
+    }
+}
+
+impl ConnectionLike for WorkerConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        self.connection()?.req_packed_command(cmd)
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        self.connection()?.req_packed_commands(cmd, offset, count)
+    }
+
+    fn get_db(&self) -> i64 {
+        self.client.get_connection_info().redis.db
+    }
+
+    fn check_connection(&mut self) -> bool {
+        self.connection()
+            .map(|connection| connection.check_connection())
+            .unwrap_or(false)
+    }
+
+    fn is_open(&self) -> bool {
+        self.connection.as_ref().is_some_and(Connection::is_open)
+    }
+}
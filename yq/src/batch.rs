@@ -0,0 +1,219 @@
+use crate::helper::{duration_ms_arg, read_redis_value_as_int, read_redis_value_as_str};
+use crate::{PreparedJob, Queue};
+use redis::{FromRedisValue, RedisResult, Script, ScriptInvocation};
+
+// Jobs join an open batch through `EnqueueManyAction::prepare_invoke_in_batch`.
+// Once sealed, the batch completes when its last job is done, dead or cancelled,
+// and the callback job becomes ready.
+#[derive(Clone)]
+pub struct BatchAction {
+    create_script: Script,
+    seal_script: Script,
+    status_script: Script,
+    queue: Queue,
+}
+
+impl BatchAction {
+    pub fn new(queue: Queue) -> Self {
+        Self {
+            create_script: Script::new(crate::lua::BATCH_CREATE),
+            seal_script: Script::new(crate::lua::BATCH_SEAL),
+            status_script: Script::new(crate::lua::BATCH_STATUS),
+            queue,
+        }
+    }
+
+    // Invocation returns the batch id
+    pub fn prepare_create(&self) -> ScriptInvocation<'_> {
+        let mut invoke = self.create_script.prepare_invoke();
+        invoke
+            .key(self.queue.batch_seq_key.as_str())
+            .key(self.queue.batches_key.as_str());
+
+        invoke
+    }
+
+    // No more jobs join the batch. The unique key of the callback is ignored.
+    pub fn prepare_seal(
+        &self,
+        batch_id: i64,
+        callback: Option<&PreparedJob>,
+        now: i64,
+    ) -> ScriptInvocation<'_> {
+        let mut invoke = self.seal_script.prepare_invoke();
+        invoke
+            .key(self.queue.batches_key.as_str())
+            .key(self.queue.batches_done_key.as_str())
+            .key(self.queue.mid_seq_key.as_str())
+            .key(self.queue.messages_key.as_str())
+            .key(self.queue.lock_times_key.as_str())
+            .key(self.queue.priorities_key.as_str())
+            .key(self.queue.waiting_key.as_str())
+            .key(self.queue.mids_ready_key.as_str())
+            .key(self.queue.mids_ready_high_key.as_str())
+            .key(self.queue.mids_ready_low_key.as_str())
+            .key(self.queue.mid_circle_key.as_str())
+            .key(self.queue.isleep_a_key.as_str())
+            .key(self.queue.isleep_b_key.as_str())
+            .key(self.queue.timeouts_key.as_str());
+
+        invoke
+            .arg(batch_id)
+            .arg(now)
+            .arg(self.queue.result_ttl.as_millis() as u64);
+
+        match callback {
+            Some(callback) => invoke
+                .arg(&callback.job_data)
+                .arg(duration_ms_arg(callback.lock))
+                .arg(callback.priority.as_str())
+                .arg(duration_ms_arg(callback.timeout)),
+            None => invoke.arg("").arg(-1).arg("").arg(-1),
+        };
+
+        invoke
+    }
+
+    pub fn prepare_status(&self, batch_id: i64) -> ScriptInvocation<'_> {
+        let mut invoke = self.status_script.prepare_invoke();
+        invoke
+            .key(self.queue.batches_key.as_str())
+            .key(self.queue.batches_done_key.as_str());
+
+        invoke.arg(batch_id);
+
+        invoke
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SealBatchStatus {
+    // Callback mid, if any
+    Sealed(Option<i64>),
+    // Every job had already settled, the callback is ready
+    Completed(Option<i64>),
+    // Unknown or already sealed
+    NotOpen,
+    Unknown(String),
+}
+
+impl TryFrom<&[redis::Value]> for SealBatchStatus {
+    type Error = redis::RedisError;
+
+    fn try_from(values: &[redis::Value]) -> Result<Self, Self::Error> {
+        let mut iter = values.iter();
+        let action =
+            read_redis_value_as_str(iter.next(), "invalid seal batch status - invalid action")?;
+
+        let status = match action.as_ref() {
+            "sealed" | "completed" => {
+                let callback = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid seal batch status - invalid callback",
+                )?;
+                let callback = (callback != -1).then_some(callback);
+                if action == "sealed" {
+                    SealBatchStatus::Sealed(callback)
+                } else {
+                    SealBatchStatus::Completed(callback)
+                }
+            }
+            "not-open" => SealBatchStatus::NotOpen,
+            _ => SealBatchStatus::Unknown(format!("{values:?}")),
+        };
+
+        Ok(status)
+    }
+}
+
+impl FromRedisValue for SealBatchStatus {
+    fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+        match v {
+            redis::Value::Bulk(bulk) => SealBatchStatus::try_from(bulk.as_slice()),
+            _ => Err(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "invalid seal batch status - invalid value type",
+                format!("{v:?}"),
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchProgress {
+    pub pending: i64,
+    pub succeeded: i64,
+    // Dead or cancelled
+    pub failed: i64,
+    pub callback: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchStatus {
+    Open(BatchProgress),
+    Sealed(BatchProgress),
+    Completed {
+        progress: BatchProgress,
+        completed_at: i64,
+    },
+    // Unknown, or completed longer than the result ttl ago
+    Missing,
+    Unknown(String),
+}
+
+impl TryFrom<&[redis::Value]> for BatchStatus {
+    type Error = redis::RedisError;
+
+    fn try_from(values: &[redis::Value]) -> Result<Self, Self::Error> {
+        let mut iter = values.iter();
+        let action = read_redis_value_as_str(iter.next(), "invalid batch status - invalid action")?;
+
+        if action == "missing" {
+            return Ok(BatchStatus::Missing);
+        }
+        if !matches!(action.as_ref(), "open" | "sealed" | "completed") {
+            return Ok(BatchStatus::Unknown(format!("{values:?}")));
+        }
+
+        let pending =
+            read_redis_value_as_int(iter.next(), "invalid batch status - invalid pending")?;
+        let succeeded =
+            read_redis_value_as_int(iter.next(), "invalid batch status - invalid succeeded")?;
+        let failed = read_redis_value_as_int(iter.next(), "invalid batch status - invalid failed")?;
+        let callback =
+            read_redis_value_as_int(iter.next(), "invalid batch status - invalid callback")?;
+        let completed_at =
+            read_redis_value_as_int(iter.next(), "invalid batch status - invalid completed_at")?;
+
+        let progress = BatchProgress {
+            pending,
+            succeeded,
+            failed,
+            callback: (callback != -1).then_some(callback),
+        };
+
+        let status = match action.as_ref() {
+            "open" => BatchStatus::Open(progress),
+            "sealed" => BatchStatus::Sealed(progress),
+            _ => BatchStatus::Completed {
+                progress,
+                completed_at,
+            },
+        };
+
+        Ok(status)
+    }
+}
+
+impl FromRedisValue for BatchStatus {
+    fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+        match v {
+            redis::Value::Bulk(bulk) => BatchStatus::try_from(bulk.as_slice()),
+            _ => Err(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "invalid batch status - invalid value type",
+                format!("{v:?}"),
+            ))),
+        }
+    }
+}
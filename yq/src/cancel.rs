@@ -0,0 +1,106 @@
+use crate::helper::{read_redis_value_as_int, read_redis_value_as_str};
+use crate::Queue;
+use redis::{FromRedisValue, RedisResult, Script, ScriptInvocation};
+
+#[derive(Clone)]
+pub struct CancelAction {
+    script: Script,
+    queue: Queue,
+}
+
+impl CancelAction {
+    pub fn new(queue: Queue) -> Self {
+        Self {
+            script: Script::new(crate::lua::CANCEL),
+            queue,
+        }
+    }
+
+    pub fn prepare_invoke(&self, mid: i64, now: i64) -> ScriptInvocation<'_> {
+        let mut invoke = self.script.prepare_invoke();
+        invoke
+            .key(self.queue.messages_key.as_str())
+            .key(self.queue.lock_times_key.as_str())
+            .key(self.queue.locks_key.as_str())
+            .key(self.queue.done_key.as_str())
+            .key(self.queue.attempts_key.as_str())
+            .key(self.queue.err_key.as_str())
+            .key(self.queue.err_messages_key.as_str())
+            .key(self.queue.schedule_key.as_str())
+            .key(self.queue.dead_key.as_str())
+            .key(self.queue.unique_key.as_str())
+            .key(self.queue.unique_mids_key.as_str())
+            .key(self.queue.priorities_key.as_str())
+            .key(self.queue.waiting_key.as_str())
+            .key(self.queue.dependents_key.as_str())
+            .key(self.queue.cancelled_key.as_str())
+            .key(self.queue.batches_key.as_str())
+            .key(self.queue.batches_done_key.as_str())
+            .key(self.queue.batch_mids_key.as_str())
+            .key(self.queue.mids_ready_key.as_str())
+            .key(self.queue.mids_ready_high_key.as_str())
+            .key(self.queue.mids_ready_low_key.as_str())
+            .key(self.queue.mid_circle_key.as_str())
+            .key(self.queue.isleep_a_key.as_str())
+            .key(self.queue.isleep_b_key.as_str())
+            .key(self.queue.timeouts_key.as_str())
+            .key(self.queue.lock_tokens_key.as_str())
+            .key(self.queue.first_failed_key.as_str());
+
+        invoke
+            .arg(mid)
+            .arg(now)
+            .arg(self.queue.result_ttl.as_millis() as u64);
+
+        invoke
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CancelStatus {
+    Cancelled,
+    // Locked by a worker until the given time
+    Running(i64),
+    Finished,
+    Missing,
+    Unknown(String),
+}
+
+impl TryFrom<&[redis::Value]> for CancelStatus {
+    type Error = redis::RedisError;
+
+    fn try_from(values: &[redis::Value]) -> Result<Self, Self::Error> {
+        let mut iter = values.iter();
+        let action =
+            read_redis_value_as_str(iter.next(), "invalid cancel status - invalid action")?;
+
+        let status = match action.as_ref() {
+            "cancelled" => CancelStatus::Cancelled,
+            "running" => {
+                let until = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid cancel status - running - invalid until",
+                )?;
+                CancelStatus::Running(until)
+            }
+            "finished" => CancelStatus::Finished,
+            "missing" => CancelStatus::Missing,
+            _ => CancelStatus::Unknown(format!("{values:?}")),
+        };
+
+        Ok(status)
+    }
+}
+
+impl FromRedisValue for CancelStatus {
+    fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+        match v {
+            redis::Value::Bulk(bulk) => CancelStatus::try_from(bulk.as_slice()),
+            _ => Err(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "invalid cancel status - invalid value type",
+                format!("{v:?}"),
+            ))),
+        }
+    }
+}
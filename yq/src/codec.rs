@@ -0,0 +1,206 @@
+use crate::{Compression, Compressor, YqError, YqResult};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// Other payloads are base64 behind "~{codec}[+{compressor}]:", so mcontent
+// stays a string with the job type readable by the scripts. Plain JSON
+// payloads carry no marker, which keeps messages from before codecs, and older
+// workers, working.
+const MARKER: char = '~';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    #[default]
+    Json,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "bincode")]
+    Bincode,
+}
+
+impl Codec {
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Json => "json",
+            #[cfg(feature = "msgpack")]
+            Codec::MessagePack => "msgpack",
+            #[cfg(feature = "cbor")]
+            Codec::Cbor => "cbor",
+            #[cfg(feature = "bincode")]
+            Codec::Bincode => "bincode",
+        }
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> YqResult<String> {
+        self.encode_with(value, None)
+    }
+
+    pub fn encode_with<T: Serialize>(
+        self,
+        value: &T,
+        compression: Option<Compression>,
+    ) -> YqResult<String> {
+        let bytes = self.to_bytes(value)?;
+
+        if let Some(compressor) = compression
+            .filter(|compression| bytes.len() >= compression.threshold)
+            .map(|compression| compression.compressor)
+        {
+            let compressed = compressor.compress(&bytes)?;
+            return Ok(format!(
+                "{MARKER}{}+{}:{}",
+                self.name(),
+                compressor.name(),
+                BASE64.encode(compressed)
+            ));
+        }
+
+        if self == Codec::Json {
+            String::from_utf8(bytes).map_err(|err| YqError::EncodeJob(err.to_string()))
+        } else {
+            Ok(format!("{MARKER}{}:{}", self.name(), BASE64.encode(bytes)))
+        }
+    }
+
+    // Whatever the codec of the job type, the one recorded in the payload
+    // decodes it. Codecs left out of the build fail as decode errors.
+    pub fn decode<T: DeserializeOwned>(payload: &str) -> YqResult<T> {
+        let Some(marked) = payload.strip_prefix(MARKER) else {
+            return serde_json::from_str(payload)
+                .map_err(|err| YqError::DecodeJob(err.to_string()));
+        };
+
+        let (name, data) = marked
+            .split_once(':')
+            .ok_or_else(|| YqError::InvalidJobData(payload.into()))?;
+        let bytes = BASE64
+            .decode(data)
+            .map_err(|err| YqError::DecodeJob(err.to_string()))?;
+
+        match name.split_once('+') {
+            Some((codec, compressor)) => {
+                Self::from_bytes(codec, &Compressor::decompress(compressor, &bytes)?)
+            }
+            None => Self::from_bytes(name, &bytes),
+        }
+    }
+
+    fn to_bytes<T: Serialize>(self, value: &T) -> YqResult<Vec<u8>> {
+        match self {
+            Codec::Json => serde_json::to_vec(value).map_err(YqError::SerializeJob),
+            #[cfg(feature = "msgpack")]
+            Codec::MessagePack => {
+                rmp_serde::to_vec_named(value).map_err(|err| YqError::EncodeJob(err.to_string()))
+            }
+            #[cfg(feature = "cbor")]
+            Codec::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(value, &mut bytes)
+                    .map(|()| bytes)
+                    .map_err(|err| YqError::EncodeJob(err.to_string()))
+            }
+            #[cfg(feature = "bincode")]
+            Codec::Bincode => {
+                bincode::serialize(value).map_err(|err| YqError::EncodeJob(err.to_string()))
+            }
+        }
+    }
+
+    fn from_bytes<T: DeserializeOwned>(name: &str, bytes: &[u8]) -> YqResult<T> {
+        match name {
+            "json" => {
+                serde_json::from_slice(bytes).map_err(|err| YqError::DecodeJob(err.to_string()))
+            }
+            #[cfg(feature = "msgpack")]
+            "msgpack" => {
+                rmp_serde::from_slice(bytes).map_err(|err| YqError::DecodeJob(err.to_string()))
+            }
+            #[cfg(feature = "cbor")]
+            "cbor" => {
+                ciborium::from_reader(bytes).map_err(|err| YqError::DecodeJob(err.to_string()))
+            }
+            #[cfg(feature = "bincode")]
+            "bincode" => {
+                bincode::deserialize(bytes).map_err(|err| YqError::DecodeJob(err.to_string()))
+            }
+            _ => Err(YqError::DecodeJob(format!("unsupported codec: {name}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        name: String,
+        values: Vec<u32>,
+    }
+
+    fn payload() -> Payload {
+        Payload {
+            name: "payload".into(),
+            values: vec![1, 2, 3],
+        }
+    }
+
+    fn codecs() -> Vec<Codec> {
+        vec![
+            Codec::Json,
+            #[cfg(feature = "msgpack")]
+            Codec::MessagePack,
+            #[cfg(feature = "cbor")]
+            Codec::Cbor,
+            #[cfg(feature = "bincode")]
+            Codec::Bincode,
+        ]
+    }
+
+    #[test]
+    fn round_trips_each_codec() {
+        for codec in codecs() {
+            let encoded = codec.encode(&payload()).unwrap();
+            let decoded: Payload = Codec::decode(&encoded).unwrap();
+            assert_eq!(decoded, payload(), "{}", codec.name());
+        }
+    }
+
+    #[test]
+    fn marks_all_but_json() {
+        for codec in codecs() {
+            let encoded = codec.encode(&payload()).unwrap();
+            match codec {
+                Codec::Json => assert!(encoded.starts_with('{'), "{encoded}"),
+                #[allow(unreachable_patterns)]
+                _ => assert!(
+                    encoded.starts_with(&format!("~{}:", codec.name())),
+                    "{encoded}"
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_legacy_json() {
+        let decoded: Payload = Codec::decode(r#"{"name":"payload","values":[1,2,3]}"#).unwrap();
+        assert_eq!(decoded, payload());
+    }
+
+    #[test]
+    fn rejects_unknown_codec() {
+        let err = Codec::decode::<Payload>("~yaml:bmFtZTogcGF5bG9hZA==").unwrap_err();
+        assert!(matches!(err, YqError::DecodeJob(_)), "{err:?}");
+    }
+
+    #[test]
+    fn rejects_marker_without_codec() {
+        let err = Codec::decode::<Payload>("~json").unwrap_err();
+        assert!(matches!(err, YqError::InvalidJobData(_)), "{err:?}");
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{YqError, YqResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compressor {
+    #[cfg(feature = "zstd")]
+    Zstd,
+    #[cfg(feature = "gzip")]
+    Gzip,
+}
+
+// Payloads of at least `threshold` encoded bytes are compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compression {
+    pub compressor: Compressor,
+    pub threshold: usize,
+}
+
+impl Compression {
+    pub const fn new(compressor: Compressor, threshold: usize) -> Self {
+        Self {
+            compressor,
+            threshold,
+        }
+    }
+}
+
+impl Compressor {
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "zstd")]
+            Compressor::Zstd => "zstd",
+            #[cfg(feature = "gzip")]
+            Compressor::Gzip => "gzip",
+        }
+    }
+
+    #[cfg_attr(not(any(feature = "zstd", feature = "gzip")), allow(unused_variables))]
+    pub(crate) fn compress(self, bytes: &[u8]) -> YqResult<Vec<u8>> {
+        match self {
+            #[cfg(feature = "zstd")]
+            Compressor::Zstd => {
+                zstd::encode_all(bytes, 0).map_err(|err| YqError::EncodeJob(err.to_string()))
+            }
+            #[cfg(feature = "gzip")]
+            Compressor::Gzip => {
+                use std::io::Write;
+
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder
+                    .write_all(bytes)
+                    .and_then(|()| encoder.finish())
+                    .map_err(|err| YqError::EncodeJob(err.to_string()))
+            }
+        }
+    }
+
+    // Compressors left out of the build fail as decode errors
+    #[cfg_attr(not(any(feature = "zstd", feature = "gzip")), allow(unused_variables))]
+    pub(crate) fn decompress(name: &str, bytes: &[u8]) -> YqResult<Vec<u8>> {
+        match name {
+            #[cfg(feature = "zstd")]
+            "zstd" => zstd::decode_all(bytes).map_err(|err| YqError::DecodeJob(err.to_string())),
+            #[cfg(feature = "gzip")]
+            "gzip" => {
+                use std::io::Read;
+
+                let mut decompressed = Vec::new();
+                flate2::read::GzDecoder::new(bytes)
+                    .read_to_end(&mut decompressed)
+                    .map(|_| decompressed)
+                    .map_err(|err| YqError::DecodeJob(err.to_string()))
+            }
+            _ => Err(YqError::DecodeJob(format!(
+                "unsupported compression: {name}"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Codec;
+
+    #[cfg(any(feature = "zstd", feature = "gzip"))]
+    fn compressors() -> Vec<Compressor> {
+        vec![
+            #[cfg(feature = "zstd")]
+            Compressor::Zstd,
+            #[cfg(feature = "gzip")]
+            Compressor::Gzip,
+        ]
+    }
+
+    #[cfg(any(feature = "zstd", feature = "gzip"))]
+    #[test]
+    fn round_trips_each_compressor() {
+        let payload = "payload ".repeat(64);
+        for compressor in compressors() {
+            let compression = Compression::new(compressor, 0);
+            let encoded = Codec::Json
+                .encode_with(&payload, Some(compression))
+                .unwrap();
+            assert!(
+                encoded.starts_with(&format!("~json+{}:", compressor.name())),
+                "{encoded}"
+            );
+            let decoded: String = Codec::decode(&encoded).unwrap();
+            assert_eq!(decoded, payload);
+        }
+    }
+
+    #[cfg(any(feature = "zstd", feature = "gzip"))]
+    #[test]
+    fn compresses_from_threshold() {
+        // 10 bytes once JSON encoded, quotes included
+        let payload = "12345678";
+        for compressor in compressors() {
+            let below = Compression::new(compressor, 11);
+            assert_eq!(
+                Codec::Json.encode_with(&payload, Some(below)).unwrap(),
+                r#""12345678""#
+            );
+
+            let at = Compression::new(compressor, 10);
+            let encoded = Codec::Json.encode_with(&payload, Some(at)).unwrap();
+            assert!(encoded.starts_with("~json+"), "{encoded}");
+            assert_eq!(Codec::decode::<String>(&encoded).unwrap(), payload);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_compressor() {
+        let err = Codec::decode::<String>("~json+lz4:AAAA").unwrap_err();
+        assert!(matches!(err, YqError::DecodeJob(_)), "{err:?}");
+    }
+}
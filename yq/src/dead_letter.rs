@@ -0,0 +1,207 @@
+use crate::helper::{read_redis_value_as_int, read_redis_value_as_str};
+use crate::Queue;
+use redis::{FromRedisValue, RedisResult, Script, ScriptInvocation};
+
+#[derive(Clone)]
+pub struct DeadLetterAction {
+    list_script: Script,
+    requeue_script: Script,
+    purge_script: Script,
+    queue: Queue,
+}
+
+impl DeadLetterAction {
+    pub fn new(queue: Queue) -> Self {
+        Self {
+            list_script: Script::new(crate::lua::DEAD_LIST),
+            requeue_script: Script::new(crate::lua::DEAD_REQUEUE),
+            purge_script: Script::new(crate::lua::DEAD_PURGE),
+            queue,
+        }
+    }
+
+    fn prepare_list_script(&self) -> ScriptInvocation<'_> {
+        let mut invoke = self.list_script.prepare_invoke();
+        invoke
+            .key(self.queue.dead_key.as_str())
+            .key(self.queue.dead_messages_key.as_str())
+            .key(self.queue.dead_errors_key.as_str())
+            .key(self.queue.dead_attempts_key.as_str())
+            .key(self.queue.first_failed_key.as_str());
+
+        invoke
+    }
+
+    // Latest dead first
+    pub fn prepare_list(&self, offset: usize, count: usize) -> ScriptInvocation<'_> {
+        let mut invoke = self.prepare_list_script();
+        invoke.arg("range").arg(offset).arg(count);
+
+        invoke
+    }
+
+    pub fn prepare_inspect(&self, mid: i64) -> ScriptInvocation<'_> {
+        let mut invoke = self.prepare_list_script();
+        invoke.arg("mid").arg(mid);
+
+        invoke
+    }
+
+    pub fn prepare_requeue(&self, mid: i64) -> ScriptInvocation<'_> {
+        let mut invoke = self.requeue_script.prepare_invoke();
+        invoke
+            .key(self.queue.dead_key.as_str())
+            .key(self.queue.dead_messages_key.as_str())
+            .key(self.queue.dead_errors_key.as_str())
+            .key(self.queue.dead_attempts_key.as_str())
+            .key(self.queue.messages_key.as_str())
+            .key(self.queue.mids_ready_key.as_str())
+            .key(self.queue.mid_circle_key.as_str())
+            .key(self.queue.isleep_a_key.as_str())
+            .key(self.queue.isleep_b_key.as_str())
+            .key(self.queue.mids_ready_high_key.as_str())
+            .key(self.queue.mids_ready_low_key.as_str())
+            .key(self.queue.priorities_key.as_str())
+            .key(self.queue.first_failed_key.as_str());
+
+        invoke.arg(mid);
+
+        invoke
+    }
+
+    fn prepare_purge_script(&self) -> ScriptInvocation<'_> {
+        let mut invoke = self.purge_script.prepare_invoke();
+        invoke
+            .key(self.queue.dead_key.as_str())
+            .key(self.queue.dead_messages_key.as_str())
+            .key(self.queue.dead_errors_key.as_str())
+            .key(self.queue.dead_attempts_key.as_str())
+            .key(self.queue.lock_times_key.as_str())
+            .key(self.queue.priorities_key.as_str())
+            .key(self.queue.timeouts_key.as_str())
+            .key(self.queue.first_failed_key.as_str());
+
+        invoke
+    }
+
+    pub fn prepare_purge(&self, mid: i64) -> ScriptInvocation<'_> {
+        let mut invoke = self.prepare_purge_script();
+        invoke.arg("mid").arg(mid);
+
+        invoke
+    }
+
+    pub fn prepare_purge_all(&self) -> ScriptInvocation<'_> {
+        let mut invoke = self.prepare_purge_script();
+        invoke.arg("all");
+
+        invoke
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeadJob {
+    pub mid: i64,
+    pub died_at: i64,
+    // Of the first failed attempt, `died_at` without retries
+    pub first_failed_at: i64,
+    pub attempts: i64,
+    pub error: String,
+    pub mcontent: String,
+}
+
+#[derive(Debug)]
+pub struct DeadJobs(pub Vec<DeadJob>);
+
+impl TryFrom<&[redis::Value]> for DeadJobs {
+    type Error = redis::RedisError;
+
+    fn try_from(values: &[redis::Value]) -> Result<Self, Self::Error> {
+        let mut iter = values.iter();
+        let mut dead_jobs = Vec::with_capacity(values.len() / 6);
+
+        while let Some(mid) = iter.next() {
+            let mid = read_redis_value_as_str(Some(mid), "invalid dead jobs - invalid mid")?;
+            let mid = mid.parse::<i64>().map_err(|err| {
+                redis::RedisError::from((
+                    redis::ErrorKind::ResponseError,
+                    "invalid dead jobs - invalid mid",
+                    err.to_string(),
+                ))
+            })?;
+            let died_at =
+                read_redis_value_as_int(iter.next(), "invalid dead jobs - invalid died_at")?;
+            let first_failed_at = read_redis_value_as_int(
+                iter.next(),
+                "invalid dead jobs - invalid first_failed_at",
+            )?;
+            let attempts =
+                read_redis_value_as_int(iter.next(), "invalid dead jobs - invalid attempts")?;
+            let error = read_redis_value_as_str(iter.next(), "invalid dead jobs - invalid error")?;
+            let mcontent =
+                read_redis_value_as_str(iter.next(), "invalid dead jobs - invalid mcontent")?;
+
+            dead_jobs.push(DeadJob {
+                mid,
+                died_at,
+                first_failed_at,
+                attempts,
+                error: error.into_owned(),
+                mcontent: mcontent.into_owned(),
+            });
+        }
+
+        Ok(DeadJobs(dead_jobs))
+    }
+}
+
+impl FromRedisValue for DeadJobs {
+    fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+        match v {
+            redis::Value::Bulk(bulk) => DeadJobs::try_from(bulk.as_slice()),
+            _ => Err(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "invalid dead jobs - invalid value type",
+                format!("{v:?}"),
+            ))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RequeueStatus {
+    Requeued,
+    Missing,
+    Unknown(String),
+}
+
+impl TryFrom<&[redis::Value]> for RequeueStatus {
+    type Error = redis::RedisError;
+
+    fn try_from(values: &[redis::Value]) -> Result<Self, Self::Error> {
+        let mut iter = values.iter();
+        let action =
+            read_redis_value_as_str(iter.next(), "invalid requeue status - invalid action")?;
+
+        let status = match action.as_ref() {
+            "requeued" => RequeueStatus::Requeued,
+            "missing" => RequeueStatus::Missing,
+            _ => RequeueStatus::Unknown(format!("{values:?}")),
+        };
+
+        Ok(status)
+    }
+}
+
+impl FromRedisValue for RequeueStatus {
+    fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+        match v {
+            redis::Value::Bulk(bulk) => RequeueStatus::try_from(bulk.as_slice()),
+            _ => Err(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "invalid requeue status - invalid value type",
+                format!("{v:?}"),
+            ))),
+        }
+    }
+}
@@ -1,6 +1,14 @@
-use crate::helper::{read_redis_value_as_int, read_redis_value_as_str};
-use crate::Queue;
+use crate::helper::{decode_job, read_redis_value_as_int, read_redis_value_as_str};
+use crate::{JobLimits, Queue};
 use redis::{FromRedisValue, RedisResult, Script, ScriptInvocation};
+use std::time::Duration;
+
+// Serve the maintenance circle at least once every this many dequeues
+const CIRCLE_EVERY: i64 = 8;
+
+// Ready mids of a job type with all concurrency slots taken are scheduled this
+// far ahead
+const CONCURRENCY_DEFER: Duration = Duration::from_secs(1);
 
 #[derive(Clone)]
 pub struct DequeueAction {
@@ -16,7 +24,8 @@ impl DequeueAction {
         }
     }
 
-    pub fn prepare_invoke(&self, now: i64) -> ScriptInvocation {
+    // `job_limits` of the job types the worker handles
+    pub fn prepare_invoke(&self, now: i64, job_limits: &JobLimits) -> ScriptInvocation<'_> {
         let mut invoke = self.script.prepare_invoke();
         invoke
             .key(self.queue.messages_key.as_str())
@@ -26,9 +35,39 @@ impl DequeueAction {
             .key(self.queue.mids_ready_key.as_str())
             .key(self.queue.mid_circle_key.as_str())
             .key(self.queue.ndry_runs_key.as_str())
-            .key(self.queue.isleep_b_key.as_str());
+            .key(self.queue.isleep_b_key.as_str())
+            .key(self.queue.attempts_key.as_str())
+            .key(self.queue.err_key.as_str())
+            .key(self.queue.err_messages_key.as_str())
+            .key(self.queue.unique_key.as_str())
+            .key(self.queue.unique_mids_key.as_str())
+            .key(self.queue.mids_ready_high_key.as_str())
+            .key(self.queue.mids_ready_low_key.as_str())
+            .key(self.queue.priorities_key.as_str())
+            .key(self.queue.circle_turn_key.as_str())
+            .key(self.queue.schedule_key.as_str())
+            .key(self.queue.timeouts_key.as_str())
+            .key(self.queue.lock_seq_key.as_str())
+            .key(self.queue.lock_tokens_key.as_str())
+            .key(self.queue.first_failed_key.as_str());
 
-        invoke.arg(now * 1000).arg(self.queue.default_lock_ms);
+        invoke
+            .arg(now)
+            .arg(self.queue.default_lock.as_millis() as u64)
+            .arg(CIRCLE_EVERY)
+            .arg(CONCURRENCY_DEFER.as_millis() as u64);
+
+        for (job_type, rate_limit, period_ms, concurrency) in job_limits.iter() {
+            invoke
+                .key(self.queue.rate_limit_key(job_type))
+                .key(self.queue.concurrency_key(job_type))
+                .key(self.queue.rate_deferred_key(job_type));
+            invoke
+                .arg(job_type)
+                .arg(rate_limit)
+                .arg(period_ms)
+                .arg(concurrency);
+        }
 
         invoke
     }
@@ -36,16 +75,59 @@ impl DequeueAction {
 
 #[derive(Clone)]
 pub struct FinishAction {
+    script: Script,
     queue: Queue,
 }
 
 impl FinishAction {
     pub fn new(queue: Queue) -> Self {
-        Self { queue }
+        Self {
+            script: Script::new(crate::lua::FINISH),
+            queue,
+        }
     }
 
-    pub fn prepare_invoke(&self, job_id: i64) -> redis::Cmd {
-        redis::Cmd::sadd(self.queue.done_key.as_str(), job_id)
+    // `result` is the serialized `Job::Output`, `token` of the dequeue that
+    // acquired the lock. Returns -1 once the lock was lost to another worker.
+    pub fn prepare_invoke(
+        &self,
+        job_id: i64,
+        token: i64,
+        job_type: &str,
+        result: &str,
+        now: i64,
+    ) -> ScriptInvocation<'_> {
+        let mut invoke = self.script.prepare_invoke();
+        invoke
+            .key(self.queue.done_key.as_str())
+            .key(self.queue.unique_key.as_str())
+            .key(self.queue.unique_mids_key.as_str())
+            .key(self.queue.result_key(job_id))
+            .key(self.queue.waiting_key.as_str())
+            .key(self.queue.dependents_key.as_str())
+            .key(self.queue.messages_key.as_str())
+            .key(self.queue.mids_ready_key.as_str())
+            .key(self.queue.mids_ready_high_key.as_str())
+            .key(self.queue.mids_ready_low_key.as_str())
+            .key(self.queue.priorities_key.as_str())
+            .key(self.queue.mid_circle_key.as_str())
+            .key(self.queue.isleep_a_key.as_str())
+            .key(self.queue.isleep_b_key.as_str())
+            .key(self.queue.batches_key.as_str())
+            .key(self.queue.batches_done_key.as_str())
+            .key(self.queue.batch_mids_key.as_str())
+            .key(self.queue.concurrency_key(job_type))
+            .key(self.queue.lock_tokens_key.as_str())
+            .key(self.queue.done_at_key.as_str());
+
+        invoke
+            .arg(job_id)
+            .arg(result)
+            .arg(self.queue.result_ttl.as_millis() as u64)
+            .arg(now)
+            .arg(token);
+
+        invoke
     }
 }
 
@@ -60,6 +142,12 @@ impl SleepOnAction {
     }
 
     pub fn prepare_invoke(&self, dequeue_sleep: DequeueSleep) -> redis::Cmd {
+        let sleep_time = self.sleep_time(&dequeue_sleep);
+        self.prepare_invoke_for(&dequeue_sleep, sleep_time)
+    }
+
+    // Seconds to sleep unless woken by new mids
+    pub fn sleep_time(&self, dequeue_sleep: &DequeueSleep) -> usize {
         let sleep_time = if dequeue_sleep.ndry_runs > 6 {
             18
         } else if dequeue_sleep.ndry_runs <= 0 {
@@ -68,7 +156,12 @@ impl SleepOnAction {
             //                        dequeue_sleep.ndry_runs
             dequeue_sleep.ndry_runs * 3
         };
+        sleep_time as usize
+    }
 
+    // Sleeps `secs` of the sleep, for workers that check for a shutdown between
+    // the parts
+    pub fn prepare_invoke_for(&self, dequeue_sleep: &DequeueSleep, secs: usize) -> redis::Cmd {
         let (src_key, dst_key) = match dequeue_sleep.sleep_on {
             SleepOn::SleepOnA => (
                 self.queue.isleep_a_key.as_str(),
@@ -80,9 +173,9 @@ impl SleepOnAction {
             ),
         };
 
-        tracing::trace!("sleep_on - {} secs", sleep_time);
+        tracing::trace!("sleep_on - {} secs", secs);
 
-        redis::Cmd::brpoplpush(src_key, dst_key, sleep_time as usize)
+        redis::Cmd::brpoplpush(src_key, dst_key, secs)
     }
 }
 
@@ -136,8 +229,15 @@ impl DequeueSleep {
 #[derive(Debug)]
 pub struct DequeueHandle {
     pub mid: i64,
+    // Empty when the mcontent cannot be decoded
+    pub job_type: String,
     pub mcontent: String,
-    _lock_ms: i64,
+    pub lock: Duration,
+    pub attempts: i64,
+    // Enqueue override of `Job::TIMEOUT`
+    pub timeout: Option<Duration>,
+    // Proves the lock is still ours to extend, finish, fail or release
+    pub token: i64,
 }
 
 impl DequeueHandle {
@@ -159,11 +259,31 @@ impl DequeueHandle {
             iter.next(),
             "invalid dequeue status - handle - invalid lock_ms",
         )?;
+        let attempts = read_redis_value_as_int(
+            iter.next(),
+            "invalid dequeue status - handle - invalid attempts",
+        )?;
+        let timeout_ms = read_redis_value_as_int(
+            iter.next(),
+            "invalid dequeue status - handle - invalid timeout_ms",
+        )?;
+        let token = read_redis_value_as_int(
+            iter.next(),
+            "invalid dequeue status - handle - invalid token",
+        )?;
+
+        let job_type = decode_job(&mcontent)
+            .map(|(job_type, _)| job_type.to_string())
+            .unwrap_or_default();
 
         Ok(DequeueHandle {
             mid,
+            job_type,
             mcontent: mcontent.into_owned(),
-            _lock_ms: lock_ms,
+            lock: Duration::from_millis(lock_ms.max(0) as u64),
+            attempts,
+            timeout: (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms as u64)),
+            token,
         })
     }
 }
@@ -237,3 +357,130 @@ impl From<&str> for SleepOn {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, EnqueueAction, EnqueueOptions, EnqueueStatus, Job, JobType, RateLimit};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct Capped;
+
+    impl Job for Capped {
+        const JOB_TYPE: JobType = JobType::Borrowed("capped");
+        type State = ();
+        type Output = ();
+        const CONCURRENCY: Option<u32> = Some(1);
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Free;
+
+    impl Job for Free {
+        const JOB_TYPE: JobType = JobType::Borrowed("free");
+        type State = ();
+        type Output = ();
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Rated;
+
+    const RATE_PERIOD_MS: i64 = 60_000;
+
+    impl Job for Rated {
+        const JOB_TYPE: JobType = JobType::Borrowed("rated");
+        type State = ();
+        type Output = ();
+        const RATE_LIMIT: Option<RateLimit> = Some(RateLimit::per_minute(2));
+    }
+
+    fn enqueue<J: Job>(con: &mut redis::Connection, queue: &Queue, job: &J) -> i64 {
+        let status: EnqueueStatus = EnqueueAction::new(queue.clone())
+            .prepare_invoke(job, &EnqueueOptions::default())
+            .unwrap()
+            .invoke(con)
+            .unwrap();
+        match status {
+            EnqueueStatus::Added(added) => added.mid,
+            status => panic!("{status:?}"),
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a redis at YQ_TEST_REDIS_URL"]
+    fn capped_job_type_does_not_block_others() {
+        let mut con = testing::connection();
+        let queue = testing::queue();
+        let mut job_limits = JobLimits::new();
+        job_limits.add::<Capped>();
+        job_limits.add::<Free>();
+
+        enqueue(&mut con, &queue, &Capped);
+        let capped = enqueue(&mut con, &queue, &Capped);
+        let free = enqueue(&mut con, &queue, &Free);
+
+        let dequeue = DequeueAction::new(queue.clone());
+        let dequeue = |con: &mut redis::Connection| -> DequeueStatus {
+            dequeue
+                .prepare_invoke(
+                    crate::unix_timestamp_ms(time::OffsetDateTime::now_utc()),
+                    &job_limits,
+                )
+                .invoke(con)
+                .unwrap()
+        };
+
+        // The first takes the only slot of its type
+        assert!(
+            matches!(dequeue(&mut con), DequeueStatus::Handle(handle) if handle.job_type == "capped")
+        );
+
+        // The second is deferred and the free one behind it handed out
+        match dequeue(&mut con) {
+            DequeueStatus::Handle(handle) => assert_eq!(handle.mid, free),
+            status => panic!("{status:?}"),
+        }
+        let deferred: Option<i64> = redis::Cmd::zscore(queue.schedule_key.as_str(), capped)
+            .query(&mut con)
+            .unwrap();
+        assert!(deferred.is_some());
+    }
+
+    #[test]
+    #[ignore = "needs a redis at YQ_TEST_REDIS_URL"]
+    fn rate_limited_mids_deferred_a_limit_per_period() {
+        let mut con = testing::connection();
+        let queue = testing::queue();
+        let mut job_limits = JobLimits::new();
+        job_limits.add::<Rated>();
+
+        let mids: Vec<i64> = (0..6).map(|_| enqueue(&mut con, &queue, &Rated)).collect();
+
+        let now = crate::unix_timestamp_ms(time::OffsetDateTime::now_utc());
+        let dequeue = DequeueAction::new(queue.clone());
+        let statuses: Vec<DequeueStatus> = (0..mids.len())
+            .map(|_| {
+                dequeue
+                    .prepare_invoke(now, &job_limits)
+                    .invoke(&mut con)
+                    .unwrap()
+            })
+            .collect();
+
+        // The limit is handed out, the rest deferred two per later period
+        for (status, mid) in statuses.iter().zip(&mids).take(2) {
+            assert!(
+                matches!(status, DequeueStatus::Handle(handle) if handle.mid == *mid),
+                "{status:?}"
+            );
+        }
+        for (i, mid) in mids.iter().enumerate().skip(2) {
+            let run_at: Option<i64> = redis::Cmd::zscore(queue.schedule_key.as_str(), *mid)
+                .query(&mut con)
+                .unwrap();
+            let periods = (i as i64 - 2) / 2 + 1;
+            assert_eq!(run_at, Some(now + periods * RATE_PERIOD_MS), "mid {mid}");
+        }
+    }
+}
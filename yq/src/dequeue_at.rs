@@ -16,12 +16,17 @@ impl DequeueAtAction {
         }
     }
 
-    pub fn prepare_invoke(&self, run_at: i64) -> ScriptInvocation {
+    pub fn prepare_invoke(&self, run_at: i64) -> ScriptInvocation<'_> {
         let mut invoke = self.script.prepare_invoke();
         invoke
             .key(self.queue.mids_ready_key.as_str())
             .key(self.queue.mid_circle_key.as_str())
-            .key(self.queue.schedule_key.as_str());
+            .key(self.queue.schedule_key.as_str())
+            .key(self.queue.mids_ready_high_key.as_str())
+            .key(self.queue.mids_ready_low_key.as_str())
+            .key(self.queue.priorities_key.as_str())
+            .key(self.queue.legacy_schedule_key.as_str())
+            .key(self.queue.messages_key.as_str());
 
         invoke.arg(run_at);
 
@@ -31,8 +36,8 @@ impl DequeueAtAction {
 
 #[derive(Debug)]
 pub enum DequeueAtStatus {
-    Dequeued(i64),
-    NoJob,
+    Dequeued { count: i64, next_at: Option<i64> },
+    NoJob { next_at: Option<i64> },
     Unknown(String),
 }
 
@@ -61,14 +66,75 @@ impl TryFrom<&[redis::Value]> for DequeueAtStatus {
             "dequeued" => {
                 let count = read_redis_value_as_int(
                     iter.next(),
-                    "invalid dequeue at status - sleep - invalid count",
+                    "invalid dequeue at status - dequeued - invalid count",
                 )?;
-                DequeueAtStatus::Dequeued(count)
+                let next_at = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid dequeue at status - dequeued - invalid next_at",
+                )?;
+                DequeueAtStatus::Dequeued {
+                    count,
+                    next_at: (next_at >= 0).then_some(next_at),
+                }
+            }
+            "no-job" => {
+                let next_at = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid dequeue at status - no-job - invalid next_at",
+                )?;
+                DequeueAtStatus::NoJob {
+                    next_at: (next_at >= 0).then_some(next_at),
+                }
             }
-            "no-job" => DequeueAtStatus::NoJob,
             _ => DequeueAtStatus::Unknown(format!("{values:?}")),
         };
 
         Ok(status)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use redis::Commands;
+
+    #[test]
+    #[ignore = "needs a redis at YQ_TEST_REDIS_URL"]
+    fn drains_legacy_schedule() {
+        let mut con = testing::connection();
+        let queue = testing::queue();
+
+        // The legacy schedule is shared with the other queues of the prefix
+        let mid = i64::from(rand::random::<u32>());
+        let other_mid = mid + 1;
+        let _: () = con.hset(queue.messages_key.as_str(), mid, "mcnt").unwrap();
+        let _: () = con
+            .zadd(queue.legacy_schedule_key.as_str(), mid, 1000)
+            .unwrap();
+        let _: () = con
+            .zadd(queue.legacy_schedule_key.as_str(), other_mid, 1000)
+            .unwrap();
+
+        let status: DequeueAtStatus = DequeueAtAction::new(queue.clone())
+            .prepare_invoke(2000)
+            .invoke(&mut con)
+            .unwrap();
+        assert!(
+            matches!(status, DequeueAtStatus::Dequeued { count: 1, .. }),
+            "{status:?}"
+        );
+
+        let ready: Vec<i64> = con.lrange(queue.mids_ready_key.as_str(), 0, -1).unwrap();
+        assert_eq!(ready, vec![mid]);
+        let left: Vec<i64> = con
+            .zrange(queue.legacy_schedule_key.as_str(), 0, -1)
+            .unwrap();
+        assert!(!left.contains(&mid));
+        assert!(left.contains(&other_mid));
+
+        let _: () = con
+            .zrem(queue.legacy_schedule_key.as_str(), other_mid)
+            .unwrap();
+    }
+}
@@ -1,5 +1,7 @@
-use crate::helper::{encode_job, read_redis_value_as_int, read_redis_value_as_str};
-use crate::{Job, Queue, YqResult};
+use crate::helper::{
+    duration_ms_arg, encode_job, read_redis_value_as_int, read_redis_value_as_str,
+};
+use crate::{EnqueueOptions, Job, Queue, YqResult};
 use redis::{FromRedisValue, RedisResult, Script, ScriptInvocation};
 
 #[derive(Clone)]
@@ -16,19 +18,50 @@ impl EnqueueAction {
         }
     }
 
-    pub fn prepare_invoke<J: Job>(&self, job: &J) -> YqResult<ScriptInvocation> {
+    pub fn prepare_invoke<J: Job>(
+        &self,
+        job: &J,
+        options: &EnqueueOptions,
+    ) -> YqResult<ScriptInvocation<'_>> {
+        let priority = options.priority.unwrap_or(J::PRIORITY);
+
         let mut invoke = self.script.prepare_invoke();
         invoke
             .key(self.queue.mid_seq_key.as_str())
             .key(self.queue.messages_key.as_str())
             .key(self.queue.lock_times_key.as_str())
             .key(self.queue.mids_ready_key.as_str())
+            .key(self.queue.mids_ready_high_key.as_str())
+            .key(self.queue.mids_ready_low_key.as_str())
             .key(self.queue.mid_circle_key.as_str())
             .key(self.queue.isleep_a_key.as_str())
-            .key(self.queue.isleep_b_key.as_str());
+            .key(self.queue.isleep_b_key.as_str())
+            .key(self.queue.done_key.as_str())
+            .key(self.queue.unique_key.as_str())
+            .key(self.queue.unique_mids_key.as_str())
+            .key(self.queue.priorities_key.as_str())
+            .key(self.queue.waiting_key.as_str())
+            .key(self.queue.dependents_key.as_str())
+            .key(self.queue.dead_key.as_str())
+            .key(self.queue.cancelled_key.as_str())
+            .key(self.queue.timeouts_key.as_str());
 
         let job_data = encode_job(job)?;
-        invoke.arg(&job_data).arg(J::LOCK_MS);
+        let unique_key = options.unique_key.clone().or_else(|| job.unique_key());
+        invoke
+            .arg(&job_data)
+            .arg(duration_ms_arg(J::LOCK))
+            .arg(unique_key.unwrap_or_default())
+            .arg(priority.as_str())
+            .arg(
+                options
+                    .depends_on
+                    .iter()
+                    .map(i64::to_string)
+                    .collect::<Vec<_>>()
+                    .join(","),
+            )
+            .arg(duration_ms_arg(options.timeout));
 
         Ok(invoke)
     }
@@ -37,6 +70,10 @@ impl EnqueueAction {
 #[derive(Debug)]
 pub enum EnqueueStatus {
     Added(EnqueueStatusAdded),
+    Exists(i64),
+    // Added, ready once its parents are done
+    Waiting(i64),
+    ParentFailed(i64),
     Unknown(String),
 }
 
@@ -63,6 +100,23 @@ impl TryFrom<&[redis::Value]> for EnqueueStatus {
                     read_redis_value_as_int(iter.next(), "invalid enqueue status - invalid mid")?;
                 EnqueueStatus::Added(EnqueueStatusAdded { mid })
             }
+            "exists" => {
+                let mid =
+                    read_redis_value_as_int(iter.next(), "invalid enqueue status - invalid mid")?;
+                EnqueueStatus::Exists(mid)
+            }
+            "waiting" => {
+                let mid =
+                    read_redis_value_as_int(iter.next(), "invalid enqueue status - invalid mid")?;
+                EnqueueStatus::Waiting(mid)
+            }
+            "parent-failed" => {
+                let parent = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid enqueue status - invalid parent",
+                )?;
+                EnqueueStatus::ParentFailed(parent)
+            }
             _ => EnqueueStatus::Unknown(format!("{values:?}")),
         };
 
@@ -1,5 +1,5 @@
-use crate::helper::{encode_job, read_redis_value_as_int, read_redis_value_as_str};
-use crate::{Job, Queue, YqResult};
+use crate::helper::{duration_ms_arg, read_redis_value_as_int, read_redis_value_as_str};
+use crate::{EnqueueOptions, Job, PreparedJob, Queue, YqResult};
 use redis::{FromRedisValue, RedisResult, Script, ScriptInvocation};
 
 #[derive(Clone)]
@@ -16,23 +16,45 @@ impl EnqueueAtAction {
         }
     }
 
-    pub fn prepare_invoke<J: Job>(&self, job: &J, run_at: i64) -> YqResult<ScriptInvocation> {
+    pub fn prepare_invoke<J: Job>(
+        &self,
+        job: &J,
+        run_at: i64,
+        options: &EnqueueOptions,
+    ) -> YqResult<ScriptInvocation<'_>> {
+        let job = PreparedJob::with_options(job, options)?;
+        Ok(self.prepare_invoke_prepared(&job, run_at))
+    }
+
+    pub fn prepare_invoke_prepared(&self, job: &PreparedJob, run_at: i64) -> ScriptInvocation<'_> {
         let mut invoke = self.script.prepare_invoke();
         invoke
             .key(self.queue.mid_seq_key.as_str())
             .key(self.queue.messages_key.as_str())
-            .key(self.queue.schedule_key.as_str());
+            .key(self.queue.schedule_key.as_str())
+            .key(self.queue.done_key.as_str())
+            .key(self.queue.unique_key.as_str())
+            .key(self.queue.unique_mids_key.as_str())
+            .key(self.queue.priorities_key.as_str())
+            .key(self.queue.lock_times_key.as_str())
+            .key(self.queue.timeouts_key.as_str());
 
-        let job_data = encode_job(job)?;
-        invoke.arg(&job_data).arg(run_at);
+        invoke
+            .arg(&job.job_data)
+            .arg(run_at)
+            .arg(job.unique_key.as_deref().unwrap_or_default())
+            .arg(job.priority.as_str())
+            .arg(duration_ms_arg(job.lock))
+            .arg(duration_ms_arg(job.timeout));
 
-        Ok(invoke)
+        invoke
     }
 }
 
 #[derive(Debug)]
 pub enum EnqueueAtStatus {
     Added(i64),
+    Exists(i64),
     Unknown(String),
 }
 
@@ -65,6 +87,13 @@ impl TryFrom<&[redis::Value]> for EnqueueAtStatus {
                 )?;
                 EnqueueAtStatus::Added(mid)
             }
+            "exists" => {
+                let mid = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid enqueue at status - invalid mid",
+                )?;
+                EnqueueAtStatus::Exists(mid)
+            }
             _ => EnqueueAtStatus::Unknown(format!("{values:?}")),
         };
 
@@ -0,0 +1,130 @@
+use crate::helper::{duration_ms_arg, encode_job};
+use crate::{EnqueueOptions, Job, Priority, Queue, YqError, YqResult};
+use redis::{Script, ScriptInvocation};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Run-at of a job pushed straight to the ready lists
+const RUN_NOW: i64 = -1;
+
+// A job encoded up front, so jobs of different types can go in one batch
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PreparedJob {
+    pub(crate) job_data: String,
+    pub(crate) lock: Option<Duration>,
+    #[serde(default)]
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) unique_key: Option<String>,
+    pub(crate) priority: Priority,
+}
+
+impl PreparedJob {
+    pub fn new<J: Job>(job: &J) -> YqResult<Self> {
+        Self::with_options(job, &EnqueueOptions::default())
+    }
+
+    pub fn with_options<J: Job>(job: &J, options: &EnqueueOptions) -> YqResult<Self> {
+        if !options.depends_on.is_empty() {
+            return Err(YqError::InvalidEnqueueOptions(
+                "depends_on is only supported by a single ready enqueue".into(),
+            ));
+        }
+
+        Ok(Self {
+            job_data: encode_job(job)?,
+            lock: J::LOCK,
+            timeout: options.timeout,
+            unique_key: options.unique_key.clone().or_else(|| job.unique_key()),
+            priority: options.priority.unwrap_or(J::PRIORITY),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct EnqueueManyAction {
+    script: Script,
+    queue: Queue,
+}
+
+impl EnqueueManyAction {
+    pub fn new(queue: Queue) -> Self {
+        Self {
+            script: Script::new(crate::lua::ENQUEUE_MANY),
+            queue,
+        }
+    }
+
+    fn prepare_script(&self, batch_id: Option<i64>) -> ScriptInvocation<'_> {
+        let mut invoke = self.script.prepare_invoke();
+        invoke
+            .key(self.queue.mid_seq_key.as_str())
+            .key(self.queue.messages_key.as_str())
+            .key(self.queue.lock_times_key.as_str())
+            .key(self.queue.mids_ready_key.as_str())
+            .key(self.queue.mids_ready_high_key.as_str())
+            .key(self.queue.mids_ready_low_key.as_str())
+            .key(self.queue.mid_circle_key.as_str())
+            .key(self.queue.isleep_a_key.as_str())
+            .key(self.queue.isleep_b_key.as_str())
+            .key(self.queue.done_key.as_str())
+            .key(self.queue.unique_key.as_str())
+            .key(self.queue.unique_mids_key.as_str())
+            .key(self.queue.priorities_key.as_str())
+            .key(self.queue.schedule_key.as_str())
+            .key(self.queue.batches_key.as_str())
+            .key(self.queue.batch_mids_key.as_str())
+            .key(self.queue.timeouts_key.as_str());
+
+        invoke.arg(batch_id.map(|id| id.to_string()).unwrap_or_default());
+
+        invoke
+    }
+
+    fn push_job(invoke: &mut ScriptInvocation<'_>, job: &PreparedJob, run_at: i64) {
+        invoke
+            .arg(&job.job_data)
+            .arg(duration_ms_arg(job.lock))
+            .arg(job.unique_key.as_deref().unwrap_or_default())
+            .arg(job.priority.as_str())
+            .arg(run_at)
+            .arg(duration_ms_arg(job.timeout));
+    }
+
+    // Invocation returns the mids in job order
+    pub fn prepare_invoke(&self, jobs: &[PreparedJob]) -> ScriptInvocation<'_> {
+        let mut invoke = self.prepare_script(None);
+        for job in jobs {
+            Self::push_job(&mut invoke, job, RUN_NOW);
+        }
+
+        invoke
+    }
+
+    // Run-at in unix milliseconds
+    pub fn prepare_invoke_at<'a>(
+        &self,
+        jobs: impl IntoIterator<Item = (&'a PreparedJob, i64)>,
+    ) -> ScriptInvocation<'_> {
+        let mut invoke = self.prepare_script(None);
+        for (job, run_at) in jobs {
+            Self::push_job(&mut invoke, job, run_at);
+        }
+
+        invoke
+    }
+
+    // Adds ready jobs to an open batch. Invocation returns the mids in job
+    // order, or a `BATCH_NOT_OPEN` error once the batch is sealed or unknown.
+    pub fn prepare_invoke_in_batch(
+        &self,
+        batch_id: i64,
+        jobs: &[PreparedJob],
+    ) -> ScriptInvocation<'_> {
+        let mut invoke = self.prepare_script(Some(batch_id));
+        for job in jobs {
+            Self::push_job(&mut invoke, job, RUN_NOW);
+        }
+
+        invoke
+    }
+}
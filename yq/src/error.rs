@@ -7,6 +7,8 @@ pub type YqResult<T> = Result<T, YqError>;
 pub enum YqError {
     #[error("CreateRedisClient")]
     CreateRedisClient(redis::RedisError),
+    #[error("InvalidQueue")]
+    InvalidQueue(String),
     #[error("GetRedisConn")]
     GetRedisConn(redis::RedisError),
     #[error("Dequeue")]
@@ -17,8 +19,30 @@ pub enum YqError {
     Enqueue(redis::RedisError),
     #[error("EnqueueAt")]
     EnqueueAt(redis::RedisError),
+    #[error("EnqueueMany")]
+    EnqueueMany(redis::RedisError),
     #[error("SerializeJob")]
     SerializeJob(serde_json::Error),
+    #[error("EncodeJob")]
+    EncodeJob(String),
+    #[error("InvalidEnqueueOptions")]
+    InvalidEnqueueOptions(String),
+    #[error("JobTimeout")]
+    JobTimeout(std::time::Duration),
+    #[error("ParentFailed")]
+    ParentFailed(i64),
+    #[error("Batch")]
+    Batch(redis::RedisError),
+    #[error("BatchNotOpen")]
+    BatchNotOpen(i64),
+    #[error("SerializeResult")]
+    SerializeResult(serde_json::Error),
+    #[error("Result")]
+    Result(redis::RedisError),
+    #[error("DecodeResult")]
+    DecodeResult(serde_json::Error),
+    #[error("WaitResultTimeout")]
+    WaitResultTimeout(i64),
     #[error("DupJobHandler")]
     DupJobType(JobType),
     #[error("JobTypeMissing")]
@@ -29,6 +53,29 @@ pub enum YqError {
     RunJobError(YqRunJobError),
     #[error("FailJobError")]
     FailJobError(redis::RedisError),
+    #[error("ExtendLock")]
+    ExtendLock(redis::RedisError),
+    #[error("DecodeJob")]
+    DecodeJob(String),
+    #[error("DeadLetter")]
+    DeadLetter(redis::RedisError),
+    #[error("Status")]
+    Status(redis::RedisError),
+    #[error("Cancel")]
+    Cancel(redis::RedisError),
+    #[error("Periodic")]
+    Periodic(redis::RedisError),
+    #[error("InvalidPeriodic")]
+    InvalidPeriodic(String),
+    #[error("Signal")]
+    Signal(std::io::Error),
+}
+
+impl YqError {
+    // Retrying a job that cannot be decoded is pointless
+    pub fn is_decode_error(&self) -> bool {
+        matches!(self, YqError::InvalidJobData(_) | YqError::DecodeJob(_))
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,93 @@
+use crate::helper::{read_redis_value_as_int, read_redis_value_as_str};
+use crate::Queue;
+use redis::{FromRedisValue, RedisResult, Script, ScriptInvocation};
+use std::time::Duration;
+
+#[derive(Clone)]
+pub struct ExtendLockAction {
+    script: Script,
+    queue: Queue,
+}
+
+impl ExtendLockAction {
+    pub fn new(queue: Queue) -> Self {
+        Self {
+            script: Script::new(crate::lua::EXTEND_LOCK),
+            queue,
+        }
+    }
+
+    // `token` of the dequeue that acquired the lock
+    pub fn prepare_invoke(
+        &self,
+        mid: i64,
+        token: i64,
+        now: i64,
+        lock: Duration,
+    ) -> ScriptInvocation<'_> {
+        let mut invoke = self.script.prepare_invoke();
+        invoke
+            .key(self.queue.messages_key.as_str())
+            .key(self.queue.locks_key.as_str())
+            .key(self.queue.done_key.as_str())
+            .key(self.queue.lock_tokens_key.as_str());
+
+        invoke
+            .arg(mid)
+            .arg(now)
+            .arg(lock.as_millis() as u64)
+            .arg(token);
+
+        invoke
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtendLockStatus {
+    // Locked until the given time
+    Extended(i64),
+    Expired,
+    // Held by a later dequeue
+    Lost,
+    Missing,
+    Unknown(String),
+}
+
+impl TryFrom<&[redis::Value]> for ExtendLockStatus {
+    type Error = redis::RedisError;
+
+    fn try_from(values: &[redis::Value]) -> Result<Self, Self::Error> {
+        let mut iter = values.iter();
+        let action =
+            read_redis_value_as_str(iter.next(), "invalid extend lock status - invalid action")?;
+
+        let status = match action.as_ref() {
+            "extended" => {
+                let exp_lock = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid extend lock status - extended - invalid exp_lock",
+                )?;
+                ExtendLockStatus::Extended(exp_lock)
+            }
+            "expired" => ExtendLockStatus::Expired,
+            "lost" => ExtendLockStatus::Lost,
+            "missing" => ExtendLockStatus::Missing,
+            _ => ExtendLockStatus::Unknown(format!("{values:?}")),
+        };
+
+        Ok(status)
+    }
+}
+
+impl FromRedisValue for ExtendLockStatus {
+    fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+        match v {
+            redis::Value::Bulk(bulk) => ExtendLockStatus::try_from(bulk.as_slice()),
+            _ => Err(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "invalid extend lock status - invalid value type",
+                format!("{v:?}"),
+            ))),
+        }
+    }
+}
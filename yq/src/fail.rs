@@ -0,0 +1,222 @@
+use crate::helper::{read_redis_value_as_int, read_redis_value_as_str};
+use crate::{Queue, RetryPolicy};
+use redis::{FromRedisValue, RedisResult, Script, ScriptInvocation};
+
+#[derive(Clone)]
+pub struct FailAction {
+    script: Script,
+    queue: Queue,
+}
+
+impl FailAction {
+    pub fn new(queue: Queue) -> Self {
+        Self {
+            script: Script::new(crate::lua::FAIL),
+            queue,
+        }
+    }
+
+    // `token` and `attempts` of the dequeue that acquired the lock
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare_invoke(
+        &self,
+        mid: i64,
+        token: i64,
+        attempts: i64,
+        job_type: &str,
+        now: i64,
+        error: &str,
+        job_data: &str,
+        retry_policy: &RetryPolicy,
+    ) -> ScriptInvocation<'_> {
+        let mut invoke = self.script.prepare_invoke();
+        invoke
+            .key(self.queue.messages_key.as_str())
+            .key(self.queue.locks_key.as_str())
+            .key(self.queue.attempts_key.as_str())
+            .key(self.queue.err_key.as_str())
+            .key(self.queue.err_messages_key.as_str())
+            .key(self.queue.mid_circle_key.as_str())
+            .key(self.queue.schedule_key.as_str())
+            .key(self.queue.dead_key.as_str())
+            .key(self.queue.dead_messages_key.as_str())
+            .key(self.queue.dead_errors_key.as_str())
+            .key(self.queue.dead_attempts_key.as_str())
+            .key(self.queue.unique_key.as_str())
+            .key(self.queue.unique_mids_key.as_str())
+            .key(self.queue.waiting_key.as_str())
+            .key(self.queue.dependents_key.as_str())
+            .key(self.queue.cancelled_key.as_str())
+            .key(self.queue.lock_times_key.as_str())
+            .key(self.queue.priorities_key.as_str())
+            .key(self.queue.batches_key.as_str())
+            .key(self.queue.batches_done_key.as_str())
+            .key(self.queue.batch_mids_key.as_str())
+            .key(self.queue.mids_ready_key.as_str())
+            .key(self.queue.mids_ready_high_key.as_str())
+            .key(self.queue.mids_ready_low_key.as_str())
+            .key(self.queue.isleep_a_key.as_str())
+            .key(self.queue.isleep_b_key.as_str())
+            .key(self.queue.concurrency_key(job_type))
+            .key(self.queue.timeouts_key.as_str())
+            .key(self.queue.lock_tokens_key.as_str())
+            .key(self.queue.first_failed_key.as_str());
+
+        invoke
+            .arg(mid)
+            .arg(now)
+            .arg(error)
+            .arg(job_data)
+            .arg(retry_policy.max_attempts)
+            .arg(retry_delay_ms(retry_policy, attempts))
+            .arg(self.queue.result_ttl.as_millis() as u64)
+            .arg(token);
+
+        invoke
+    }
+}
+
+// Delay of the retry when `attempts` is not the last, saturated for a delay
+// past the end of time
+fn retry_delay_ms(retry_policy: &RetryPolicy, attempts: i64) -> u64 {
+    let attempts = u32::try_from(attempts).unwrap_or(u32::MAX);
+    u64::try_from(retry_policy.retry_delay(attempts).as_millis()).unwrap_or(u64::MAX)
+}
+
+#[derive(Debug)]
+pub enum FailStatus {
+    Retry { attempts: i64, run_at: i64 },
+    Dead { attempts: i64 },
+    Skip(String),
+    Unknown(String),
+}
+
+impl TryFrom<&[redis::Value]> for FailStatus {
+    type Error = redis::RedisError;
+
+    fn try_from(values: &[redis::Value]) -> Result<Self, Self::Error> {
+        let mut iter = values.iter();
+        let action = read_redis_value_as_str(iter.next(), "invalid fail status - invalid action")?;
+
+        let status = match action.as_ref() {
+            "retry" => {
+                let attempts = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid fail status - retry - invalid attempts",
+                )?;
+                let run_at = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid fail status - retry - invalid run_at",
+                )?;
+                FailStatus::Retry { attempts, run_at }
+            }
+            "dead" => {
+                let attempts = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid fail status - dead - invalid attempts",
+                )?;
+                FailStatus::Dead { attempts }
+            }
+            "skip" => {
+                let reason = read_redis_value_as_str(
+                    iter.next(),
+                    "invalid fail status - skip - invalid reason",
+                )?;
+                FailStatus::Skip(reason.into_owned())
+            }
+            _ => FailStatus::Unknown(format!("{values:?}")),
+        };
+
+        Ok(status)
+    }
+}
+
+impl FromRedisValue for FailStatus {
+    fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+        match v {
+            redis::Value::Bulk(bulk) => FailStatus::try_from(bulk.as_slice()),
+            _ => Err(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "invalid fail status - invalid value type",
+                format!("{v:?}"),
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing, unix_timestamp_ms, DequeueAction, DequeueStatus, EnqueueAction, EnqueueOptions,
+        Job, JobLimits, JobType,
+    };
+    use serde::{Deserialize, Serialize};
+    use std::time::Duration;
+
+    #[derive(Serialize, Deserialize)]
+    struct Noop;
+
+    impl Job for Noop {
+        const JOB_TYPE: JobType = JobType::Borrowed("noop");
+        type State = ();
+        type Output = ();
+    }
+
+    #[test]
+    #[ignore = "needs a redis at YQ_TEST_REDIS_URL"]
+    fn retry_scheduled_after_policy_delay() {
+        let mut con = testing::connection();
+        let queue = testing::queue();
+        let retry_policy = RetryPolicy::new(3)
+            .base_delay(Duration::from_secs(5))
+            .jitter(0.0);
+
+        let _: redis::Value = EnqueueAction::new(queue.clone())
+            .prepare_invoke(&Noop, &EnqueueOptions::default())
+            .unwrap()
+            .invoke(&mut con)
+            .unwrap();
+
+        let fail = FailAction::new(queue.clone());
+        let mut now = unix_timestamp_ms(time::OffsetDateTime::now_utc());
+        for (attempt, delay_ms) in [(1, 5_000), (2, 10_000)] {
+            let status: DequeueStatus = DequeueAction::new(queue.clone())
+                .prepare_invoke(now, &JobLimits::new())
+                .invoke(&mut con)
+                .unwrap();
+            let DequeueStatus::Handle(handle) = status else {
+                panic!("{status:?}");
+            };
+            assert_eq!(handle.attempts, attempt);
+
+            let status: FailStatus = fail
+                .prepare_invoke(
+                    handle.mid,
+                    handle.token,
+                    handle.attempts,
+                    "noop",
+                    now,
+                    "error",
+                    "",
+                    &retry_policy,
+                )
+                .invoke(&mut con)
+                .unwrap();
+            match status {
+                FailStatus::Retry { attempts, run_at } => {
+                    assert_eq!(attempts, attempt);
+                    assert_eq!(run_at, now + delay_ms);
+                }
+                status => panic!("{status:?}"),
+            }
+
+            // Due, back to the ready list
+            now += delay_ms;
+            let _: redis::Value = crate::DequeueAtAction::new(queue.clone())
+                .prepare_invoke(now)
+                .invoke(&mut con)
+                .unwrap();
+        }
+    }
+}
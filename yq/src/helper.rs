@@ -2,6 +2,8 @@ use crate::error::{YqError, YqResult};
 use crate::Job;
 use redis::RedisResult;
 use std::borrow::Cow;
+use std::time::Duration;
+use time::OffsetDateTime;
 
 pub fn decode_job(mcontent: &str) -> YqResult<(&str, &str)> {
     let (job_type_len, rest_mcontent) = match mcontent.split_once(':') {
@@ -19,10 +21,23 @@ pub fn decode_job(mcontent: &str) -> YqResult<(&str, &str)> {
 }
 
 pub(crate) fn encode_job<J: Job>(job: &J) -> YqResult<String> {
-    let job_str = serde_json::to_string(job).map_err(YqError::SerializeJob)?;
+    let job_str = J::CODEC.encode_with(job, J::COMPRESSION)?;
     Ok(format!("{}:{}{}", J::JOB_TYPE.len(), J::JOB_TYPE, job_str))
 }
 
+// Schedule scores, lock expiries and run-at times are unix milliseconds
+pub fn unix_timestamp_ms(at: OffsetDateTime) -> i64 {
+    (at.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+// -1 for none, the job or queue default applies
+pub(crate) fn duration_ms_arg(duration: Option<Duration>) -> i64 {
+    duration
+        .filter(|duration| !duration.is_zero())
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or(-1)
+}
+
 pub(crate) fn read_redis_value_as_str<'a>(
     v: Option<&'a redis::Value>,
     err_desc: &'static str,
@@ -0,0 +1,47 @@
+use crate::{Job, JobType, RateLimit};
+
+#[derive(Clone, Debug)]
+struct JobTypeLimits {
+    job_type: JobType,
+    rate_limit: Option<RateLimit>,
+    concurrency: Option<u32>,
+}
+
+// Rate and concurrency limits of the job types a worker handles, enforced by
+// `DequeueAction`
+#[derive(Clone, Debug, Default)]
+pub struct JobLimits(Vec<JobTypeLimits>);
+
+impl JobLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<J: Job>(&mut self) {
+        if J::RATE_LIMIT.is_none() && J::CONCURRENCY.is_none() {
+            return;
+        }
+
+        self.0.push(JobTypeLimits {
+            job_type: J::JOB_TYPE,
+            rate_limit: J::RATE_LIMIT,
+            concurrency: J::CONCURRENCY,
+        });
+    }
+
+    // {(job-type, rate-limit, rate-period-ms, concurrency)}, -1 for no limit
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, i64, i64, i64)> {
+        self.0.iter().map(|limits| {
+            let (limit, period_ms) = match limits.rate_limit {
+                Some(rate_limit) => (
+                    rate_limit.limit() as i64,
+                    rate_limit.period().as_millis() as i64,
+                ),
+                None => (-1, -1),
+            };
+            let concurrency = limits.concurrency.map(i64::from).unwrap_or(-1);
+
+            (limits.job_type.as_ref(), limit, period_ms, concurrency)
+        })
+    }
+}
@@ -1,24 +1,63 @@
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::time::Duration;
 
+mod batch;
+mod cancel;
+mod codec;
+mod compression;
+mod dead_letter;
 mod dequeue;
 mod dequeue_at;
 mod enqueue;
 mod enqueue_at;
+mod enqueue_many;
 pub(crate) mod error;
+mod extend_lock;
+mod fail;
 mod helper;
+mod job_limits;
 pub(crate) mod lua;
+mod options;
+mod periodic;
+mod priority;
 pub(crate) mod queue;
+mod rate_limit;
 mod redis_keys;
+mod release;
+mod result;
+mod retry;
+mod status;
+#[cfg(test)]
+mod testing;
 
 pub use {
-    dequeue::{DequeueAction, DequeueSleep, DequeueStatus, FinishAction, SleepOnAction},
+    batch::{BatchAction, BatchProgress, BatchStatus, SealBatchStatus},
+    cancel::{CancelAction, CancelStatus},
+    codec::Codec,
+    compression::{Compression, Compressor},
+    dead_letter::{DeadJob, DeadJobs, DeadLetterAction, RequeueStatus},
+    dequeue::{
+        DequeueAction, DequeueHandle, DequeueSleep, DequeueStatus, FinishAction, SleepOnAction,
+    },
     dequeue_at::{DequeueAtAction, DequeueAtStatus},
     enqueue::{EnqueueAction, EnqueueStatus},
     enqueue_at::{EnqueueAtAction, EnqueueAtStatus},
+    enqueue_many::{EnqueueManyAction, PreparedJob},
     error::{YqError, YqResult, YqRunJobError},
-    helper::decode_job,
-    queue::Queue,
+    extend_lock::{ExtendLockAction, ExtendLockStatus},
+    fail::{FailAction, FailStatus},
+    helper::{decode_job, unix_timestamp_ms},
+    job_limits::JobLimits,
+    options::EnqueueOptions,
+    periodic::{PeriodicAction, PeriodicClaimStatus, PeriodicJob, Recurrence},
+    priority::Priority,
+    queue::{Queue, QueueBuilder},
+    rate_limit::RateLimit,
+    release::{ReleaseAction, ReleaseStatus},
+    result::ResultAction,
+    retry::RetryPolicy,
+    status::{JobStatus, StatusAction},
 };
 
 pub type JobType = std::borrow::Cow<'static, str>;
@@ -28,7 +67,46 @@ pub trait Job: Serialize + DeserializeOwned {
 
     type State: Clone + 'static;
 
-    const LOCK_MS: isize = -1;
+    // Kept for `result` after the job is done, `()` for none. An output
+    // serialized to `null` is not kept.
+    type Output: Serialize + DeserializeOwned;
+
+    // How long a dequeued mid stays locked before it is handed out again,
+    // `None` uses the queue default lock
+    const LOCK: Option<Duration> = None;
+
+    // Handlers running longer fail with `YqError::JobTimeout` and are retried
+    // by the retry policy. A sync handler cannot be stopped, it is left running
+    // on its own thread and may still run when the job is retried. `None` for
+    // no timeout.
+    const TIMEOUT: Option<Duration> = None;
+
+    const RETRY_POLICY: RetryPolicy = RetryPolicy::DEFAULT;
+
+    const PRIORITY: Priority = Priority::Normal;
+
+    // Encodes enqueued jobs. Workers decode with the codec recorded in each
+    // message, so changing it does not strand jobs already queued.
+    const CODEC: Codec = Codec::Json;
+
+    // Large payloads are compressed before they reach the messages hash,
+    // `None` for never
+    const COMPRESSION: Option<Compression> = None;
+
+    // Shared by every worker of the queue, `None` for no limit
+    const RATE_LIMIT: Option<RateLimit> = None;
+
+    // Most mids of the type locked at once across all workers of the queue.
+    // The rest are deferred to the schedule for a moment and moved back by
+    // yq-scheduler, so they never run unless one serves the queue. `None` for
+    // no limit.
+    const CONCURRENCY: Option<u32> = None;
+
+    // Jobs with the same key are not enqueued twice while one is queued,
+    // scheduled or running
+    fn unique_key(&self) -> Option<String> {
+        None
+    }
 }
 
 pub(crate) type ArcString = std::sync::Arc<String>;
@@ -0,0 +1,11 @@
+-- KEYS
+local q_batch_seq_key = KEYS[1];
+local q_batches_key = KEYS[2];
+
+--------------------------------------------------------------------------------
+-- Return batch id
+
+local batch = tonumber(redis.call('incr', q_batch_seq_key));
+redis.call('hset', q_batches_key, batch .. ':pending', 0);
+
+return batch;
@@ -0,0 +1,75 @@
+-- KEYS
+local q_batches_key = KEYS[1];
+local q_batches_done_key = KEYS[2];
+local q_mid_seq_key = KEYS[3];
+local q_messages_key = KEYS[4];
+local q_lock_times_key = KEYS[5];
+local q_priorities_key = KEYS[6];
+local q_waiting_key = KEYS[7];
+local q_mids_ready_key = KEYS[8];
+local q_mids_ready_high_key = KEYS[9];
+local q_mids_ready_low_key = KEYS[10];
+local q_mid_circle_key = KEYS[11];
+local q_isleep_a_key = KEYS[12];
+local q_isleep_b_key = KEYS[13];
+local q_timeouts_key = KEYS[14];
+
+-- ARGV
+local batch = ARGV[1];
+local now = tonumber(ARGV[2]);
+local result_ttl_ms = tonumber(ARGV[3]);
+local mcnt_arg = ARGV[4]; -- Callback job, '' for none
+local lock_ms = tonumber(ARGV[5]);
+local priority_arg = ARGV[6];
+local timeout_ms = tonumber(ARGV[7]);
+
+-- Keys of the common helpers
+q.priorities_key = q_priorities_key;
+q.mids_ready_key = q_mids_ready_key;
+q.mids_ready_high_key = q_mids_ready_high_key;
+q.mids_ready_low_key = q_mids_ready_low_key;
+q.isleep_a_key = q_isleep_a_key;
+q.isleep_b_key = q_isleep_b_key;
+q.mid_circle_key = q_mid_circle_key;
+q.batches_key = q_batches_key;
+q.batches_done_key = q_batches_done_key;
+q.waiting_key = q_waiting_key;
+q.messages_key = q_messages_key;
+
+--------------------------------------------------------------------------------
+-- Return {action, callback-mid}, -1 without a callback
+
+if (redis.call('hexists', q_batches_key, batch .. ':pending') == 0 or
+    redis.call('hexists', q_batches_key, batch .. ':sealed') == 1) then
+    return {'not-open'};
+end
+redis.call('hset', q_batches_key, batch .. ':sealed', 1);
+
+-- Held in waiting, without parents, until complete_batch releases it
+local callback = -1;
+if (mcnt_arg ~= '') then
+    callback = tonumber(redis.call('incr', q_mid_seq_key));
+
+    redis.call('hset', q_messages_key, callback, mcnt_arg);
+    if (priority_arg ~= 'normal') then
+        redis.call('hset', q_priorities_key, callback, priority_arg);
+    end
+    if (lock_ms ~= -1) then
+        redis.call('hset', q_lock_times_key, callback, lock_ms);
+    end
+    if (timeout_ms ~= -1) then
+        redis.call('hset', q_timeouts_key, callback, timeout_ms);
+    end
+
+    redis.call('hset', q_waiting_key, callback, 1);
+    redis.call('hset', q_batches_key, batch .. ':callback', callback);
+end
+
+if (tonumber(redis.call('hget', q_batches_key, batch .. ':pending')) > 0) then
+    return {'sealed', callback};
+end
+
+complete_batch(batch, now, result_ttl_ms);
+wake_ready();
+
+return {'completed', callback};
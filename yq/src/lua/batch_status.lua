@@ -0,0 +1,34 @@
+-- KEYS
+local q_batches_key = KEYS[1];
+local q_batches_done_key = KEYS[2];
+
+-- ARGV
+local batch = ARGV[1];
+
+--------------------------------------------------------------------------------
+-- Return {state, pending, succeeded, failed, callback-mid, completed-at}
+
+local fields = redis.call('hmget', q_batches_key,
+    batch .. ':pending', batch .. ':succeeded', batch .. ':failed',
+    batch .. ':sealed', batch .. ':callback');
+if (not fields[1]) then
+    return {'missing'};
+end
+
+local completed_at = redis.call('zscore', q_batches_done_key, batch);
+
+local state = 'open';
+if (completed_at) then
+    state = 'completed';
+elseif (fields[4]) then
+    state = 'sealed';
+end
+
+return {
+    state,
+    tonumber(fields[1]),
+    tonumber(fields[2]) or 0,
+    tonumber(fields[3]) or 0,
+    tonumber(fields[5]) or -1,
+    tonumber(completed_at) or -1,
+};
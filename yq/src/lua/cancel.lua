@@ -0,0 +1,99 @@
+-- KEYS
+local q_messages_key = KEYS[1];
+local q_lock_times_key = KEYS[2];
+local q_locks_key = KEYS[3];
+local q_done_key = KEYS[4];
+local q_attempts_key = KEYS[5];
+local q_err_key = KEYS[6];
+local q_err_messages_key = KEYS[7];
+local q_schedule_key = KEYS[8];
+local q_dead_key = KEYS[9];
+local q_unique_key = KEYS[10];
+local q_unique_mids_key = KEYS[11];
+local q_priorities_key = KEYS[12];
+local q_waiting_key = KEYS[13];
+local q_dependents_key = KEYS[14];
+local q_cancelled_key = KEYS[15];
+local q_batches_key = KEYS[16];
+local q_batches_done_key = KEYS[17];
+local q_batch_mids_key = KEYS[18];
+local q_mids_ready_key = KEYS[19];
+local q_mids_ready_high_key = KEYS[20];
+local q_mids_ready_low_key = KEYS[21];
+local q_mid_circle_key = KEYS[22];
+local q_isleep_a_key = KEYS[23];
+local q_isleep_b_key = KEYS[24];
+local q_timeouts_key = KEYS[25];
+local q_lock_tokens_key = KEYS[26];
+local q_first_failed_key = KEYS[27];
+
+-- ARGV
+local mid = ARGV[1];
+local now_i = tonumber(ARGV[2]);
+local result_ttl_ms = tonumber(ARGV[3]);
+
+-- Keys of the common helpers
+q.unique_key = q_unique_key;
+q.unique_mids_key = q_unique_mids_key;
+q.priorities_key = q_priorities_key;
+q.mids_ready_key = q_mids_ready_key;
+q.mids_ready_high_key = q_mids_ready_high_key;
+q.mids_ready_low_key = q_mids_ready_low_key;
+q.isleep_a_key = q_isleep_a_key;
+q.isleep_b_key = q_isleep_b_key;
+q.mid_circle_key = q_mid_circle_key;
+q.batches_key = q_batches_key;
+q.batches_done_key = q_batches_done_key;
+q.batch_mids_key = q_batch_mids_key;
+q.waiting_key = q_waiting_key;
+q.messages_key = q_messages_key;
+q.dependents_key = q_dependents_key;
+q.lock_times_key = q_lock_times_key;
+q.timeouts_key = q_timeouts_key;
+q.cancelled_key = q_cancelled_key;
+
+--------------------------------------------------------------------------------
+
+if (redis.call('hexists', q_messages_key, mid) == 0) then
+    if (redis.call('zscore', q_dead_key, mid) or
+        redis.call('zscore', q_cancelled_key, mid)) then
+        return {'finished'};
+    end
+    return {'missing'};
+end
+
+if (redis.call('sismember', q_done_key, mid) == 1) then
+    return {'finished'};
+end
+
+local exp_lock = tonumber(redis.call('hget', q_locks_key, mid)) or 0;
+if (now_i < exp_lock) then
+    return {'running', exp_lock};
+end
+
+-- Queued, scheduled or waiting on parents. A mid left in mids-ready or
+-- mid-circle is GC'd by dequeue as msg-missing.
+redis.call('zrem', q_schedule_key,     mid);
+redis.call('hdel', q_messages_key,     mid);
+redis.call('hdel', q_lock_times_key,   mid);
+redis.call('hdel', q_timeouts_key,     mid);
+redis.call('hdel', q_locks_key,        mid);
+redis.call('hdel', q_lock_tokens_key,  mid);
+redis.call('hdel', q_attempts_key,     mid);
+redis.call('hdel', q_err_key,          mid);
+redis.call('hdel', q_first_failed_key, mid);
+redis.call('hdel', q_err_messages_key, mid);
+redis.call('hdel', q_priorities_key,   mid);
+redis.call('hdel', q_waiting_key,      mid);
+release_unique(mid);
+
+redis.call('zadd', q_cancelled_key, now_i, mid);
+settle_batch(mid, 'failed', now_i, result_ttl_ms);
+cancel_dependents(mid, now_i, result_ttl_ms);
+
+-- Cancelled mids are remembered as long as a result would be
+redis.call('zremrangebyscore', q_cancelled_key, '-inf', now_i - result_ttl_ms);
+
+wake_ready();
+
+return {'cancelled'};
@@ -0,0 +1,203 @@
+-- Helpers shared by the scripts, prepended by lua/mod.rs. They read their keys
+-- from `q`, which a script fills in from its KEYS before calling them.
+local q = {};
+
+-- Set by push_ready, wake_ready then lets sleeping workers pick the mids up
+local any_ready = false;
+
+-- q: unique_key, unique_mids_key
+local release_unique = function (mid)
+    local unique_key = redis.call('hget', q.unique_mids_key, mid);
+    if (unique_key) then
+        if (redis.call('hget', q.unique_key, unique_key) == mid) then
+            redis.call('hdel', q.unique_key, unique_key);
+        end
+        redis.call('hdel', q.unique_mids_key, mid);
+    end
+end
+
+-- q: priorities_key, mids_ready_key, mids_ready_high_key, mids_ready_low_key
+local push_ready = function (mid)
+    local priority = redis.call('hget', q.priorities_key, mid);
+    if (priority == 'high') then
+        redis.call('lpush', q.mids_ready_high_key, mid);
+    elseif (priority == 'low') then
+        redis.call('lpush', q.mids_ready_low_key, mid);
+    else
+        redis.call('lpush', q.mids_ready_key, mid);
+    end
+    any_ready = true;
+end
+
+-- q: isleep_a_key, isleep_b_key
+local interrupt_sleep = function ()
+    if redis.call('rpoplpush', q.isleep_a_key, q.isleep_b_key) then
+        return 'to_sleep_b'
+    elseif redis.call('rpoplpush', q.isleep_b_key, q.isleep_a_key) then
+        return 'to_sleep_a'
+    else   redis.call('lpush',     q.isleep_a_key, '_');
+        return 'to_sleep_a_init'
+    end
+end
+
+-- q: mid_circle_key, isleep_a_key, isleep_b_key
+local wake_ready = function ()
+    if (any_ready) then
+        if redis.call('exists', q.mid_circle_key) ~= 1 then
+            redis.call('lpush',  q.mid_circle_key, 'end-of-circle');
+        end
+        interrupt_sleep();
+    end
+end
+
+-- Adds a message that is ready for a run_at of -1, scheduled at run_at
+-- otherwise, or waiting while some of its parents (comma separated mids, ''
+-- for none) are not done. It joins the open batch, '' for none.
+-- Returns {'exists', mid} while an equal job is still queued, scheduled or
+-- running, {'parent-failed', parent} for a parent that died or was cancelled,
+-- {'waiting', mid} or {'added', mid} otherwise.
+-- q: mid_seq_key, messages_key, done_key, unique_key, unique_mids_key,
+--    priorities_key, lock_times_key, timeouts_key,
+--    schedule_key with a run_at, push_ready's without,
+--    dead_key, cancelled_key, waiting_key, dependents_key with parents,
+--    batches_key, batch_mids_key with a batch
+local schedule_message = function (mcnt, run_at, unique_key, priority, lock_ms, timeout_ms,
+                                   depends_on, batch)
+    if (unique_key ~= '') then
+        local existing_mid = redis.call('hget', q.unique_key, unique_key);
+        if (existing_mid and
+            redis.call('hexists', q.messages_key, existing_mid) == 1 and
+            redis.call('sismember', q.done_key, existing_mid) == 0) then
+            return {'exists', tonumber(existing_mid)};
+        end
+    end
+
+    -- A parent that died or was cancelled never releases its dependents
+    local parents = {};
+    for parent in string.gmatch(depends_on, '[^,]+') do
+        if (redis.call('zscore', q.dead_key, parent) or
+            redis.call('zscore', q.cancelled_key, parent)) then
+            return {'parent-failed', tonumber(parent)};
+        end
+        -- Done, or already GC'd after done
+        if (redis.call('hexists', q.messages_key, parent) == 1 and
+            redis.call('sismember', q.done_key, parent) == 0) then
+            table.insert(parents, parent);
+        end
+    end
+
+    local mid = tonumber(redis.call('incr', q.mid_seq_key));
+
+    redis.call('hset', q.messages_key, mid, mcnt);
+
+    -- Kept for re-delivery through the schedule (retries, requeue)
+    if (priority ~= 'normal') then
+        redis.call('hset', q.priorities_key, mid, priority);
+    end
+
+    if (lock_ms ~= -1) then
+        redis.call('hset', q.lock_times_key, mid, lock_ms);
+    end
+
+    if (timeout_ms ~= -1) then
+        redis.call('hset', q.timeouts_key, mid, timeout_ms);
+    end
+
+    if (unique_key ~= '') then
+        redis.call('hset', q.unique_key,      unique_key, mid);
+        redis.call('hset', q.unique_mids_key, mid, unique_key);
+    end
+
+    if (batch ~= '') then
+        redis.call('hset',    q.batch_mids_key, mid, batch);
+        redis.call('hincrby', q.batches_key,    batch .. ':pending', 1);
+    end
+
+    if (#parents > 0) then
+        -- Pushed to its ready list by finish once the last parent is done
+        redis.call('hset', q.waiting_key, mid, #parents);
+        for i, parent in ipairs(parents) do
+            local dependents = redis.call('hget', q.dependents_key, parent);
+            if (dependents) then
+                redis.call('hset', q.dependents_key, parent, dependents .. ',' .. mid);
+            else
+                redis.call('hset', q.dependents_key, parent, mid);
+            end
+        end
+        return {'waiting', mid};
+    end
+
+    if (run_at == -1) then
+        push_ready(mid);
+    else
+        redis.call('zadd', q.schedule_key, run_at, mid);
+    end
+
+    return {'added', mid};
+end
+
+-- The callback waits until the batch completes. Completed batches are
+-- trimmed once they are older than results.
+-- q: batches_key, batches_done_key, waiting_key, messages_key, push_ready's
+local complete_batch = function (batch, now, result_ttl_ms)
+    redis.call('zadd', q.batches_done_key, now, batch);
+
+    local expired = redis.call('zrangebyscore', q.batches_done_key, '-inf', now - result_ttl_ms);
+    for i, old in ipairs(expired) do
+        redis.call('hdel', q.batches_key,
+            old .. ':pending', old .. ':succeeded', old .. ':failed',
+            old .. ':sealed', old .. ':callback');
+    end
+    redis.call('zremrangebyscore', q.batches_done_key, '-inf', now - result_ttl_ms);
+
+    local callback = redis.call('hget', q.batches_key, batch .. ':callback');
+    if (callback and
+        redis.call('hdel', q.waiting_key, callback) == 1 and
+        redis.call('hexists', q.messages_key, callback) == 1) then
+        push_ready(callback);
+    end
+end
+
+-- outcome: 'succeeded' or 'failed'
+-- q: batch_mids_key, complete_batch's
+local settle_batch = function (mid, outcome, now, result_ttl_ms)
+    local batch = redis.call('hget', q.batch_mids_key, mid);
+    if (not batch) then
+        return;
+    end
+    redis.call('hdel', q.batch_mids_key, mid);
+
+    redis.call('hincrby', q.batches_key, batch .. ':' .. outcome, 1);
+    local pending = redis.call('hincrby', q.batches_key, batch .. ':pending', -1);
+    if (pending <= 0 and redis.call('hexists', q.batches_key, batch .. ':sealed') == 1) then
+        complete_batch(batch, now, result_ttl_ms);
+    end
+end
+
+-- Waiting dependents can never run, cancelled along with their own dependents
+-- q: dependents_key, lock_times_key, timeouts_key, cancelled_key,
+--    release_unique's, settle_batch's
+local cancel_dependents;
+cancel_dependents = function (mid, now, result_ttl_ms)
+    local dependents = redis.call('hget', q.dependents_key, mid);
+    if (not dependents) then
+        return;
+    end
+    redis.call('hdel', q.dependents_key, mid);
+
+    for child in string.gmatch(dependents, '[^,]+') do
+        if (redis.call('hdel', q.waiting_key, child) == 1) then
+            redis.call('hdel', q.messages_key,   child);
+            redis.call('hdel', q.lock_times_key, child);
+            redis.call('hdel', q.timeouts_key,   child);
+            redis.call('hdel', q.priorities_key, child);
+            release_unique(child);
+            redis.call('zadd', q.cancelled_key, now, child);
+            settle_batch(child, 'failed', now, result_ttl_ms);
+            cancel_dependents(child, now, result_ttl_ms);
+        end
+    end
+end
+
+--------------------------------------------------------------------------------
+
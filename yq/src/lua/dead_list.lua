@@ -0,0 +1,42 @@
+-- KEYS
+local q_dead_key = KEYS[1];
+local q_dead_messages_key = KEYS[2];
+local q_dead_errors_key = KEYS[3];
+local q_dead_attempts_key = KEYS[4];
+local q_first_failed_key = KEYS[5];
+
+-- ARGV
+local mode_arg = ARGV[1];
+
+--------------------------------------------------------------------------------
+-- Return {mid, died-at, first-failed-at, attempts, error, mcontent, ...}, latest
+-- first. Mids dead before first-failed was kept report died-at.
+
+local mids;
+if (mode_arg == 'mid') then
+    mids = {ARGV[2]};
+else
+    local start = tonumber(ARGV[2]);
+    local count = tonumber(ARGV[3]);
+    if (count > 0) then
+        mids = redis.call('zrevrange', q_dead_key, start, start + count - 1);
+    else
+        mids = {};
+    end
+end
+
+local result = {};
+for i, mid in ipairs(mids) do
+    local died_at = redis.call('zscore', q_dead_key, mid);
+    if (died_at) then
+        table.insert(result, mid);
+        table.insert(result, tonumber(died_at));
+        table.insert(result,
+            tonumber(redis.call('hget', q_first_failed_key, mid)) or tonumber(died_at));
+        table.insert(result, tonumber(redis.call('hget', q_dead_attempts_key, mid)) or 0);
+        table.insert(result, redis.call('hget', q_dead_errors_key, mid) or '');
+        table.insert(result, redis.call('hget', q_dead_messages_key, mid) or '');
+    end
+end
+
+return result;
@@ -0,0 +1,38 @@
+-- KEYS
+local q_dead_key = KEYS[1];
+local q_dead_messages_key = KEYS[2];
+local q_dead_errors_key = KEYS[3];
+local q_dead_attempts_key = KEYS[4];
+local q_lock_times_key = KEYS[5];
+local q_priorities_key = KEYS[6];
+local q_timeouts_key = KEYS[7];
+local q_first_failed_key = KEYS[8];
+
+-- ARGV
+local mode_arg = ARGV[1];
+
+--------------------------------------------------------------------------------
+-- Return number of purged mids
+
+local mids;
+if (mode_arg == 'mid') then
+    mids = {ARGV[2]};
+else
+    mids = redis.call('zrange', q_dead_key, 0, -1);
+end
+
+local count = 0;
+for i, mid in ipairs(mids) do
+    if (redis.call('zrem', q_dead_key, mid) == 1) then
+        redis.call('hdel', q_dead_messages_key, mid);
+        redis.call('hdel', q_dead_errors_key,   mid);
+        redis.call('hdel', q_dead_attempts_key, mid);
+        redis.call('hdel', q_lock_times_key,    mid);
+        redis.call('hdel', q_priorities_key,    mid);
+        redis.call('hdel', q_timeouts_key,      mid);
+        redis.call('hdel', q_first_failed_key,  mid);
+        count = count + 1;
+    end
+end
+
+return count;
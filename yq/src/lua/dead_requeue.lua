@@ -0,0 +1,49 @@
+-- KEYS
+local q_dead_key = KEYS[1];
+local q_dead_messages_key = KEYS[2];
+local q_dead_errors_key = KEYS[3];
+local q_dead_attempts_key = KEYS[4];
+local q_messages_key = KEYS[5];
+local q_mids_ready_key = KEYS[6];
+local q_mid_circle_key = KEYS[7];
+local q_isleep_a_key = KEYS[8];
+local q_isleep_b_key = KEYS[9];
+local q_mids_ready_high_key = KEYS[10];
+local q_mids_ready_low_key = KEYS[11];
+local q_priorities_key = KEYS[12];
+local q_first_failed_key = KEYS[13];
+
+-- ARGV
+local mid = ARGV[1];
+
+-- Keys of the common helpers
+q.priorities_key = q_priorities_key;
+q.mids_ready_key = q_mids_ready_key;
+q.mids_ready_high_key = q_mids_ready_high_key;
+q.mids_ready_low_key = q_mids_ready_low_key;
+q.isleep_a_key = q_isleep_a_key;
+q.isleep_b_key = q_isleep_b_key;
+
+--------------------------------------------------------------------------------
+
+local mcontent = redis.call('hget', q_dead_messages_key, mid);
+if (not mcontent) then
+    return {'missing', mid};
+end
+
+redis.call('zrem', q_dead_key,          mid);
+redis.call('hdel', q_dead_messages_key, mid);
+redis.call('hdel', q_dead_errors_key,   mid);
+redis.call('hdel', q_dead_attempts_key, mid);
+redis.call('hdel', q_first_failed_key,  mid);
+
+if redis.call('exists', q_mid_circle_key) ~= 1 then
+    redis.call('lpush',  q_mid_circle_key, 'end-of-circle');
+end
+
+-- Same mid, fresh attempts
+redis.call('hset', q_messages_key, mid, mcontent);
+push_ready(mid);
+
+interrupt_sleep();
+return {'requeued', mid};
@@ -7,47 +7,168 @@ local q_mids_ready_key = KEYS[5];
 local q_mid_circle_key = KEYS[6];
 local q_ndry_runs_key = KEYS[7];
 local q_isleep_b_key = KEYS[8];
+local q_attempts_key = KEYS[9];
+local q_err_key = KEYS[10];
+local q_err_messages_key = KEYS[11];
+local q_unique_key = KEYS[12];
+local q_unique_mids_key = KEYS[13];
+local q_mids_ready_high_key = KEYS[14];
+local q_mids_ready_low_key = KEYS[15];
+local q_priorities_key = KEYS[16];
+local q_circle_turn_key = KEYS[17];
+local q_schedule_key = KEYS[18];
+local q_timeouts_key = KEYS[19];
+local q_lock_seq_key = KEYS[20];
+local q_lock_tokens_key = KEYS[21];
+local q_first_failed_key = KEYS[22];
+-- KEYS[23..] {rate:{type}, running:{type}, rate-deferred:{type}} of each limited
+-- job type, in ARGV order
 
 -- ARGV
 local now_arg = ARGV[1];
 local default_lock_ms_arg = ARGV[2];
+local circle_every = tonumber(ARGV[3]);
+local concurrency_defer_ms = tonumber(ARGV[4]);
+-- ARGV[5..] {job-type, rate-limit, rate-period-ms, concurrency}, repeated per
+-- limited job type, -1 for no limit
+local LIMIT_ARGS = 4;
+local LIMITS_AT = 5;
 
--- Prioritize mids from ready list
-local mid = redis.call('rpoplpush', q_mids_ready_key, q_mid_circle_key) or
-        redis.call('rpoplpush', q_mid_circle_key, q_mid_circle_key);
+local limits = {};
+for i = LIMITS_AT, #ARGV, LIMIT_ARGS do
+    local n = (i - LIMITS_AT) / LIMIT_ARGS;
+    limits[ARGV[i]] = {
+        rate_key = KEYS[23 + n * 3],
+        rate_limit = tonumber(ARGV[i + 1]),
+        period_ms = tonumber(ARGV[i + 2]),
+        running_key = KEYS[24 + n * 3],
+        deferred_key = KEYS[25 + n * 3],
+        concurrency = tonumber(ARGV[i + 3]),
+    };
+end
 
-if ((not mid) or (mid == 'end-of-circle')) then -- Uninit'd or eoq
-    local new_ndry_runs = tonumber(redis.call('incr', q_ndry_runs_key));
+-- Most concurrency limited mids deferred by one dequeue
+local MAX_DEFERRED = 16;
 
-    local isleep_on;
-    if (redis.call('llen', q_isleep_b_key) == 0) then isleep_on = 'b'; else isleep_on = 'a'; end
+-- Keys of the common helpers
+q.unique_key = q_unique_key;
+q.unique_mids_key = q_unique_mids_key;
 
-    return {'sleep', 'end-of-circle', isleep_on, new_ndry_runs};
+-- mcontent is "{len(job-type)}:{job-type}{job-data}"
+local job_type_of = function (mcontent)
+    local sep = string.find(mcontent, ':', 1, true);
+    if (not sep) then
+        return false;
+    end
+    local len = tonumber(string.sub(mcontent, 1, sep - 1));
+    if (not len) then
+        return false;
+    end
+    return string.sub(mcontent, sep + 1, sep + len);
 end
 
--- From msg_status.lua ---------------------------------------------------------
-local now_i = tonumber(now_arg);
+local sleep_on = function ()
+    if (redis.call('llen', q_isleep_b_key) == 0) then return 'b'; else return 'a'; end
+end
 
-local status;
+-- Prioritize mids from ready lists, highest first, then the circle. Returns the
+-- mid and its ready list, if popped from one.
+local pop_mid = function ()
+    for i, key in ipairs({q_mids_ready_high_key, q_mids_ready_key, q_mids_ready_low_key}) do
+        local mid = redis.call('rpoplpush', key, q_mid_circle_key);
+        if (mid) then
+            return mid, key;
+        end
+    end
+    return redis.call('rpoplpush', q_mid_circle_key, q_mid_circle_key), false;
+end
 
-if (redis.call('hexists', q_messages_key, mid) == 1) then
-    if (redis.call('sismember', q_done_key, mid) == 1) then
-        status = 'done';
-    else
+local now_i = tonumber(now_arg);
+
+-- From msg_status.lua ---------------------------------------------------------
+local status_of = function (mid)
+    if (redis.call('hexists', q_messages_key, mid) == 1) then
+        if (redis.call('sismember', q_done_key, mid) == 1) then
+            return 'done';
+        end
         local exp_lock = tonumber(redis.call('hget', q_locks_key, mid)) or 0;
         if (now_i < exp_lock) then
-            status = 'locked';
-        else
-            status = 'queued';
+            return 'locked';
         end
+        return 'queued';
     end
-else
-    status = 'nx';
+    return 'nx';
 end
 --------------------------------------------------------------------------------
 
-if (status == 'locked') then
-    return {'skip', 'locked', mid};
+-- All slots of the job type taken, once the slots of holders whose lock expired,
+-- the mid itself on re-delivery, are freed
+local slots_taken = function (limit, mid)
+    if ((not limit) or limit.concurrency == -1) then
+        return false;
+    end
+    for i, holder in ipairs(redis.call('smembers', limit.running_key)) do
+        local exp_lock = tonumber(redis.call('hget', q_locks_key, holder)) or 0;
+        if (holder == mid or exp_lock <= now_i) then
+            redis.call('srem', limit.running_key, holder);
+        end
+    end
+    return redis.call('scard', limit.running_key) >= limit.concurrency;
+end
+
+-- Every `circle_every` turns the maintenance circle goes first, so GC and
+-- lock-expiry re-delivery keep moving while the ready lists are busy
+local mid = false;
+local ready_key = false;
+if (tonumber(redis.call('incr', q_circle_turn_key)) % circle_every == 0) then
+    mid = redis.call('rpoplpush', q_mid_circle_key, q_mid_circle_key);
+    if (mid == 'end-of-circle') then mid = false; end -- Ready lists decide on sleep
+end
+if (not mid) then
+    mid, ready_key = pop_mid();
+end
+
+local status;
+local mcontent;
+local limit;
+local ndeferred = 0;
+while (true) do
+    if ((not mid) or (mid == 'end-of-circle')) then -- Uninit'd or eoq
+        local new_ndry_runs = tonumber(redis.call('incr', q_ndry_runs_key));
+
+        return {'sleep', 'end-of-circle', sleep_on(), new_ndry_runs};
+    end
+
+    status = status_of(mid);
+    if (status == 'locked') then
+        return {'skip', 'locked', mid};
+    end
+
+    mcontent = false;
+    limit = false;
+    if (status == 'queued') then
+        mcontent = redis.call('hget', q_messages_key, mid);
+        limit = limits[job_type_of(mcontent) or ''];
+    end
+
+    if (not slots_taken(limit, mid)) then
+        break;
+    end
+
+    -- A re-delivered mid stays in the circle. A ready mid is deferred, without
+    -- an attempt, as a rate limited one is, and the next mid is served, so other
+    -- job types behind it keep moving.
+    if (not ready_key) then
+        return {'skip', 'concurrency-limited', mid};
+    end
+    redis.call('lrem', q_mid_circle_key, 1, mid);
+    redis.call('zadd', q_schedule_key, now_i + concurrency_defer_ms, mid);
+
+    ndeferred = ndeferred + 1;
+    if (ndeferred >= MAX_DEFERRED) then
+        return {'skip', 'concurrency-limited', mid};
+    end
+    mid, ready_key = pop_mid();
 end
 
 redis.call('set', q_ndry_runs_key, 0); -- Doing useful work
@@ -56,24 +177,65 @@ if (status == 'done') then
     -- {done, -bo, -rq} -> full GC now
     redis.call('hdel',  q_messages_key,      mid);
     redis.call('hdel',  q_lock_times_key,    mid);
+    redis.call('hdel',  q_timeouts_key,      mid);
     redis.call('hdel',  q_locks_key,         mid);
+    redis.call('hdel',  q_lock_tokens_key,   mid);
     redis.call('srem',  q_done_key,          mid);
+    redis.call('hdel',  q_attempts_key,      mid);
+    redis.call('hdel',  q_err_key,           mid);
+    redis.call('hdel',  q_err_messages_key,  mid);
+    redis.call('hdel',  q_first_failed_key,  mid);
+    redis.call('hdel',  q_priorities_key,    mid);
+    release_unique(mid);
     redis.call('ltrim', q_mid_circle_key, 1, -1);
     return {'skip', 'did-gc', mid};
 elseif (status == 'nx') then
     redis.call('hdel',  q_lock_times_key,    mid);
+    redis.call('hdel',  q_timeouts_key,      mid);
     redis.call('hdel',  q_locks_key,         mid);
+    redis.call('hdel',  q_lock_tokens_key,   mid);
     redis.call('srem',  q_done_key,          mid);
+    redis.call('hdel',  q_attempts_key,      mid);
+    redis.call('hdel',  q_first_failed_key,  mid);
+    redis.call('hdel',  q_priorities_key,    mid);
+    release_unique(mid);
     redis.call('ltrim', q_mid_circle_key, 1, -1);
     return {'skip', 'msg-missing', mid};
 elseif (status == 'queued') then
     -- {queued, -bo, _rq} -> handle now
+    if (limit and limit.rate_limit ~= -1) then
+        redis.call('zremrangebyscore', limit.rate_key, '-inf', now_i - limit.period_ms);
+        if (redis.call('zcard', limit.rate_key) >= limit.rate_limit) then
+            -- Deferred, without an attempt, until the oldest entry leaves the
+            -- window. Each later period takes the next rate_limit deferred mids,
+            -- so they are not all due, and deferred again, at once.
+            local oldest = redis.call('zrange', limit.rate_key, 0, 0, 'WITHSCORES');
+            local deferred = redis.call('incr', limit.deferred_key) - 1;
+            local run_at = (tonumber(oldest[2]) or now_i) + limit.period_ms +
+                limit.period_ms * math.floor(deferred / limit.rate_limit);
+            redis.call('pexpireat', limit.deferred_key, run_at);
+            redis.call('lrem', q_mid_circle_key, 1, mid);
+            redis.call('zadd', q_schedule_key, run_at, mid);
+            return {'skip', 'rate-limited', mid};
+        end
+        redis.call('zadd',    limit.rate_key, now_i, mid .. ':' .. now_i);
+        redis.call('pexpire', limit.rate_key, limit.period_ms);
+    end
+
+    if (limit and limit.concurrency ~= -1) then
+        redis.call('sadd', limit.running_key, mid);
+    end
+
     local lock_ms = tonumber(redis.call('hget', q_lock_times_key, mid)) or tonumber(default_lock_ms_arg);
 
     redis.call('hset',    q_locks_key,     mid, now_i + lock_ms); -- Acquire
-    local mcontent  = redis.call('hget',    q_messages_key,  mid);
+    -- Only the holder of the token may extend, finish, fail or release the lock
+    local token = redis.call('incr', q_lock_seq_key);
+    redis.call('hset',    q_lock_tokens_key, mid, token);
+    local attempts  = redis.call('hincrby', q_attempts_key,  mid, 1);
+    local timeout_ms = tonumber(redis.call('hget', q_timeouts_key, mid)) or -1;
 
-    return {'handle', mid, mcontent, lock_ms};
+    return {'handle', mid, mcontent, lock_ms, attempts, timeout_ms, token};
 else
     return {'unexpected', status, mid};
 end
\ No newline at end of file
@@ -1,25 +1,59 @@
 -- KEYS
 local q_mids_ready_key = KEYS[1];
 local q_mid_circle_key = KEYS[2];
-local schedule_key = KEYS[3];
+local q_schedule_key = KEYS[3];
+local q_mids_ready_high_key = KEYS[4];
+local q_mids_ready_low_key = KEYS[5];
+local q_priorities_key = KEYS[6];
+local q_legacy_schedule_key = KEYS[7];
+local q_messages_key = KEYS[8];
 
 -- ARGV
 local run_at = tonumber(ARGV[1]);
 
+-- Keys of the common helpers
+q.priorities_key = q_priorities_key;
+q.mids_ready_key = q_mids_ready_key;
+q.mids_ready_high_key = q_mids_ready_high_key;
+q.mids_ready_low_key = q_mids_ready_low_key;
+
 --------------------------------------------------------------------------------
+
 if redis.call('exists', q_mid_circle_key) ~= 1 then
     redis.call('lpush',  q_mid_circle_key, 'end-of-circle');
 end
 
-local mids = redis.call("ZRANGEBYSCORE", schedule_key, 0, run_at);
+local mids = redis.call("ZRANGEBYSCORE", q_schedule_key, 0, run_at);
 local count = 0;
 for i, mid in ipairs(mids) do
-    redis.call('lpush', q_mids_ready_key, mid);
+    push_ready(mid);
     count = count + 1
 end
 if count > 0 then
-    redis.call('ZREMRANGEBYSCORE', schedule_key, 0, run_at);
-    return {'dequeued', count};
+    redis.call('ZREMRANGEBYSCORE', q_schedule_key, 0, run_at);
+end
+
+-- Mids scheduled before the schedule moved per queue. The legacy schedule is
+-- shared by all queues of the prefix, only mids with a message in this queue
+-- are taken. It is not part of next_at, a later mid waits for the next poll.
+local legacy_mids = redis.call("ZRANGEBYSCORE", q_legacy_schedule_key, 0, run_at);
+for i, mid in ipairs(legacy_mids) do
+    if (redis.call('hexists', q_messages_key, mid) == 1) then
+        redis.call('zrem', q_legacy_schedule_key, mid);
+        push_ready(mid);
+        count = count + 1
+    end
+end
+
+-- Run-at of the next scheduled mid, -1 when the schedule is empty
+local next_at = -1;
+local next = redis.call('zrange', q_schedule_key, 0, 0, 'WITHSCORES');
+if (next[2]) then
+    next_at = tonumber(next[2]);
+end
+
+if count > 0 then
+    return {'dequeued', count, next_at};
 else
-    return {'no-job'};
+    return {'no-job', next_at};
 end
@@ -3,42 +3,60 @@ local q_mid_seq_key = KEYS[1];
 local q_messages_key = KEYS[2];
 local q_lock_times_key = KEYS[3];
 local q_mids_ready_key = KEYS[4];
-local q_mid_circle_key = KEYS[5];
-local q_isleep_a_key = KEYS[6];
-local q_isleep_b_key = KEYS[7];
+local q_mids_ready_high_key = KEYS[5];
+local q_mids_ready_low_key = KEYS[6];
+local q_mid_circle_key = KEYS[7];
+local q_isleep_a_key = KEYS[8];
+local q_isleep_b_key = KEYS[9];
+local q_done_key = KEYS[10];
+local q_unique_key = KEYS[11];
+local q_unique_mids_key = KEYS[12];
+local q_priorities_key = KEYS[13];
+local q_waiting_key = KEYS[14];
+local q_dependents_key = KEYS[15];
+local q_dead_key = KEYS[16];
+local q_cancelled_key = KEYS[17];
+local q_timeouts_key = KEYS[18];
 
 -- ARGV
 local mcnt_arg = ARGV[1];
-local lock_ms_arg = ARGV[2];
+local lock_ms = tonumber(ARGV[2]);
+local unique_key_arg = ARGV[3];
+local priority_arg = ARGV[4];
+local depends_on_arg = ARGV[5]; -- Comma separated parent mids
+local timeout_ms = tonumber(ARGV[6]);
+
+-- Keys of the common helpers
+q.mid_seq_key = q_mid_seq_key;
+q.messages_key = q_messages_key;
+q.lock_times_key = q_lock_times_key;
+q.mids_ready_key = q_mids_ready_key;
+q.mids_ready_high_key = q_mids_ready_high_key;
+q.mids_ready_low_key = q_mids_ready_low_key;
+q.isleep_a_key = q_isleep_a_key;
+q.isleep_b_key = q_isleep_b_key;
+q.done_key = q_done_key;
+q.unique_key = q_unique_key;
+q.unique_mids_key = q_unique_mids_key;
+q.priorities_key = q_priorities_key;
+q.waiting_key = q_waiting_key;
+q.dependents_key = q_dependents_key;
+q.dead_key = q_dead_key;
+q.cancelled_key = q_cancelled_key;
+q.timeouts_key = q_timeouts_key;
 
 --------------------------------------------------------------------------------
 -- Return {action, error}
 
-local interrupt_sleep = function ()
-    if redis.call('rpoplpush', q_isleep_a_key, q_isleep_b_key) then
-        return 'to_sleep_b'
-    elseif redis.call('rpoplpush', q_isleep_b_key, q_isleep_a_key) then
-        return 'to_sleep_a'
-    else   redis.call('lpush',     q_isleep_a_key, '_');
-        return 'to_sleep_a_init'
-    end
+local result = schedule_message(mcnt_arg, -1, unique_key_arg, priority_arg, lock_ms, timeout_ms,
+                                depends_on_arg, '');
+if (result[1] ~= 'added') then
+    return result;
 end
 
 if redis.call('exists', q_mid_circle_key) ~= 1 then
     redis.call('lpush',  q_mid_circle_key, 'end-of-circle');
 end
 
-local mid = tonumber(redis.call('incr', q_mid_seq_key));
-redis.call('lpush', q_mids_ready_key, mid); -- -> Priority queue
-
-redis.call('hset',   q_messages_key, mid, mcnt_arg);
-
-local lock_ms = tonumber(lock_ms_arg);
-if   (lock_ms ~= -1) then
-    redis.call('hset', q_lock_times_key, mid, lock_ms);
-else
-    redis.call('hdel', q_lock_times_key, mid);
-end
-
 local to_sleep = interrupt_sleep();
-return {'added', to_sleep, mid};
+return {'added', to_sleep, result[2]};
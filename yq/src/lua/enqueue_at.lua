@@ -1,18 +1,33 @@
 -- KEYS
 local q_mid_seq_key = KEYS[1];
 local q_messages_key = KEYS[2];
-local schedule_key = KEYS[3];
+local q_schedule_key = KEYS[3];
+local q_done_key = KEYS[4];
+local q_unique_key = KEYS[5];
+local q_unique_mids_key = KEYS[6];
+local q_priorities_key = KEYS[7];
+local q_lock_times_key = KEYS[8];
+local q_timeouts_key = KEYS[9];
 
 -- ARGV
 local mcnt_arg = ARGV[1];
 local run_at = tonumber(ARGV[2]);
+local unique_key_arg = ARGV[3];
+local priority_arg = ARGV[4];
+local lock_ms = tonumber(ARGV[5]);
+local timeout_ms = tonumber(ARGV[6]);
 
---------------------------------------------------------------------------------
-
-local mid = tonumber(redis.call('incr', q_mid_seq_key));
+-- Keys of the common helpers
+q.mid_seq_key = q_mid_seq_key;
+q.messages_key = q_messages_key;
+q.schedule_key = q_schedule_key;
+q.done_key = q_done_key;
+q.unique_key = q_unique_key;
+q.unique_mids_key = q_unique_mids_key;
+q.priorities_key = q_priorities_key;
+q.lock_times_key = q_lock_times_key;
+q.timeouts_key = q_timeouts_key;
 
-redis.call('hset', q_messages_key, mid, mcnt_arg);
-
-redis.call('zadd', schedule_key, run_at, mid);
+--------------------------------------------------------------------------------
 
-return { 'added', mid };
+return schedule_message(mcnt_arg, run_at, unique_key_arg, priority_arg, lock_ms, timeout_ms, '', '');
@@ -0,0 +1,74 @@
+-- KEYS
+local q_mid_seq_key = KEYS[1];
+local q_messages_key = KEYS[2];
+local q_lock_times_key = KEYS[3];
+local q_mids_ready_key = KEYS[4];
+local q_mids_ready_high_key = KEYS[5];
+local q_mids_ready_low_key = KEYS[6];
+local q_mid_circle_key = KEYS[7];
+local q_isleep_a_key = KEYS[8];
+local q_isleep_b_key = KEYS[9];
+local q_done_key = KEYS[10];
+local q_unique_key = KEYS[11];
+local q_unique_mids_key = KEYS[12];
+local q_priorities_key = KEYS[13];
+local q_schedule_key = KEYS[14];
+local q_batches_key = KEYS[15];
+local q_batch_mids_key = KEYS[16];
+local q_timeouts_key = KEYS[17];
+
+-- ARGV
+local batch_arg = ARGV[1]; -- Open batch the new mids join, '' for none
+-- {mcontent, lock-ms, unique-key, priority, run-at, timeout-ms}, repeated
+-- per job. A run-at of -1 enqueues the job as ready.
+local JOB_ARGS = 6;
+
+-- Keys of the common helpers
+q.mid_seq_key = q_mid_seq_key;
+q.messages_key = q_messages_key;
+q.lock_times_key = q_lock_times_key;
+q.mids_ready_key = q_mids_ready_key;
+q.mids_ready_high_key = q_mids_ready_high_key;
+q.mids_ready_low_key = q_mids_ready_low_key;
+q.mid_circle_key = q_mid_circle_key;
+q.isleep_a_key = q_isleep_a_key;
+q.isleep_b_key = q_isleep_b_key;
+q.done_key = q_done_key;
+q.unique_key = q_unique_key;
+q.unique_mids_key = q_unique_mids_key;
+q.priorities_key = q_priorities_key;
+q.schedule_key = q_schedule_key;
+q.batches_key = q_batches_key;
+q.batch_mids_key = q_batch_mids_key;
+q.timeouts_key = q_timeouts_key;
+
+--------------------------------------------------------------------------------
+-- Return {mid, ...} in job order, the existing mid for a unique duplicate
+
+if (batch_arg ~= '' and
+    (redis.call('hexists', q_batches_key, batch_arg .. ':pending') == 0 or
+     redis.call('hexists', q_batches_key, batch_arg .. ':sealed') == 1)) then
+    return redis.error_reply('BATCH_NOT_OPEN ' .. batch_arg);
+end
+
+local mids = {};
+
+for i = 2, #ARGV, JOB_ARGS do
+    local mcnt_arg = ARGV[i];
+    local lock_ms = tonumber(ARGV[i + 1]);
+    local unique_key_arg = ARGV[i + 2];
+    local priority_arg = ARGV[i + 3];
+    local run_at = tonumber(ARGV[i + 4]);
+    local timeout_ms = tonumber(ARGV[i + 5]);
+
+    -- An equal job still queued, scheduled or running (or earlier in this
+    -- call) gives its mid, it does not join the batch
+    local result = schedule_message(mcnt_arg, run_at, unique_key_arg, priority_arg, lock_ms,
+                                    timeout_ms, '', batch_arg);
+    table.insert(mids, result[2]);
+end
+
+-- Wake sleeping workers once for the whole batch
+wake_ready();
+
+return mids;
@@ -0,0 +1,35 @@
+-- KEYS
+local q_messages_key = KEYS[1];
+local q_locks_key = KEYS[2];
+local q_done_key = KEYS[3];
+local q_lock_tokens_key = KEYS[4];
+
+-- ARGV
+local mid = ARGV[1];
+local now_i = tonumber(ARGV[2]);
+local lock_ms = tonumber(ARGV[3]);
+local token_arg = ARGV[4];
+
+--------------------------------------------------------------------------------
+
+if (redis.call('hexists', q_messages_key, mid) == 0 or
+    redis.call('sismember', q_done_key, mid) == 1) then
+    return {'missing'};
+end
+
+-- Handed to another worker by a later dequeue
+if (redis.call('hget', q_lock_tokens_key, mid) ~= token_arg) then
+    return {'lost'};
+end
+
+-- Once expired the mid may already be handed to another worker
+local exp_lock = tonumber(redis.call('hget', q_locks_key, mid)) or 0;
+if (now_i >= exp_lock) then
+    return {'expired'};
+end
+
+-- Never shorten a lock extended further by an explicit call
+local new_exp_lock = math.max(exp_lock, now_i + lock_ms);
+redis.call('hset', q_locks_key, mid, new_exp_lock);
+
+return {'extended', new_exp_lock};
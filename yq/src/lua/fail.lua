@@ -0,0 +1,123 @@
+-- KEYS
+local q_messages_key = KEYS[1];
+local q_locks_key = KEYS[2];
+local q_attempts_key = KEYS[3];
+local q_err_key = KEYS[4];
+local q_err_messages_key = KEYS[5];
+local q_mid_circle_key = KEYS[6];
+local q_schedule_key = KEYS[7];
+local q_dead_key = KEYS[8];
+local q_dead_messages_key = KEYS[9];
+local q_dead_errors_key = KEYS[10];
+local q_dead_attempts_key = KEYS[11];
+local q_unique_key = KEYS[12];
+local q_unique_mids_key = KEYS[13];
+local q_waiting_key = KEYS[14];
+local q_dependents_key = KEYS[15];
+local q_cancelled_key = KEYS[16];
+local q_lock_times_key = KEYS[17];
+local q_priorities_key = KEYS[18];
+local q_batches_key = KEYS[19];
+local q_batches_done_key = KEYS[20];
+local q_batch_mids_key = KEYS[21];
+local q_mids_ready_key = KEYS[22];
+local q_mids_ready_high_key = KEYS[23];
+local q_mids_ready_low_key = KEYS[24];
+local q_isleep_a_key = KEYS[25];
+local q_isleep_b_key = KEYS[26];
+local q_running_key = KEYS[27];
+local q_timeouts_key = KEYS[28];
+local q_lock_tokens_key = KEYS[29];
+local q_first_failed_key = KEYS[30];
+
+-- ARGV
+local mid = ARGV[1];
+local now = tonumber(ARGV[2]);
+local error_arg = ARGV[3];
+local job_data_arg = ARGV[4];
+local max_attempts = tonumber(ARGV[5]);
+local retry_delay_ms = tonumber(ARGV[6]); -- RetryPolicy::retry_delay of the attempt
+local result_ttl_ms = tonumber(ARGV[7]);
+local token_arg = ARGV[8];
+
+-- Keys of the common helpers
+q.unique_key = q_unique_key;
+q.unique_mids_key = q_unique_mids_key;
+q.priorities_key = q_priorities_key;
+q.mids_ready_key = q_mids_ready_key;
+q.mids_ready_high_key = q_mids_ready_high_key;
+q.mids_ready_low_key = q_mids_ready_low_key;
+q.isleep_a_key = q_isleep_a_key;
+q.isleep_b_key = q_isleep_b_key;
+q.mid_circle_key = q_mid_circle_key;
+q.batches_key = q_batches_key;
+q.batches_done_key = q_batches_done_key;
+q.batch_mids_key = q_batch_mids_key;
+q.waiting_key = q_waiting_key;
+q.messages_key = q_messages_key;
+q.dependents_key = q_dependents_key;
+q.lock_times_key = q_lock_times_key;
+q.timeouts_key = q_timeouts_key;
+q.cancelled_key = q_cancelled_key;
+
+--------------------------------------------------------------------------------
+
+local mcontent = redis.call('hget', q_messages_key, mid);
+if (not mcontent) then
+    return {'skip', 'msg-missing'};
+end
+
+-- Handed to another worker by a later dequeue, which now owns the attempt
+if (redis.call('hget', q_lock_tokens_key, mid) ~= token_arg) then
+    return {'skip', 'lock-lost'};
+end
+
+local attempts = tonumber(redis.call('hget', q_attempts_key, mid)) or 1;
+
+-- Kept through the retries and in the dead letter
+redis.call('hsetnx', q_first_failed_key, mid, now);
+
+-- Out of the circle until the retry is due, or for good
+redis.call('hdel', q_locks_key, mid);
+redis.call('hdel', q_lock_tokens_key, mid);
+redis.call('lrem', q_mid_circle_key, 0, mid);
+
+-- A freed slot lets a held back mid of the job type run
+if (redis.call('srem', q_running_key, mid) == 1) then
+    interrupt_sleep();
+end
+
+if (attempts < max_attempts) then
+    redis.call('hset', q_err_key, mid, error_arg);
+    if (job_data_arg ~= '') then
+        redis.call('hset', q_err_messages_key, mid, job_data_arg);
+    end
+
+    local run_at = now + retry_delay_ms;
+    redis.call('zadd', q_schedule_key, run_at, mid);
+    return {'retry', attempts, run_at};
+end
+
+-- Exhausted, move out of the live queue into the dead letter
+-- (lock-times and timeouts are kept for a requeue, dropped on purge;
+-- first-failed is kept for the listing, dropped on either)
+redis.call('zadd', q_dead_key,          now, mid);
+redis.call('hset', q_dead_messages_key, mid, mcontent);
+redis.call('hset', q_dead_errors_key,   mid, error_arg);
+redis.call('hset', q_dead_attempts_key, mid, attempts);
+
+redis.call('hdel', q_messages_key,      mid);
+redis.call('hdel', q_attempts_key,      mid);
+redis.call('hdel', q_err_key,           mid);
+redis.call('hdel', q_err_messages_key,  mid);
+release_unique(mid);
+
+settle_batch(mid, 'failed', now, result_ttl_ms);
+cancel_dependents(mid, now, result_ttl_ms);
+
+-- Cancelled mids are remembered as long as a result would be
+redis.call('zremrangebyscore', q_cancelled_key, '-inf', now - result_ttl_ms);
+
+wake_ready();
+
+return {'dead', attempts};
@@ -0,0 +1,95 @@
+-- KEYS
+local q_done_key = KEYS[1];
+local q_unique_key = KEYS[2];
+local q_unique_mids_key = KEYS[3];
+local q_result_key = KEYS[4];
+local q_waiting_key = KEYS[5];
+local q_dependents_key = KEYS[6];
+local q_messages_key = KEYS[7];
+local q_mids_ready_key = KEYS[8];
+local q_mids_ready_high_key = KEYS[9];
+local q_mids_ready_low_key = KEYS[10];
+local q_priorities_key = KEYS[11];
+local q_mid_circle_key = KEYS[12];
+local q_isleep_a_key = KEYS[13];
+local q_isleep_b_key = KEYS[14];
+local q_batches_key = KEYS[15];
+local q_batches_done_key = KEYS[16];
+local q_batch_mids_key = KEYS[17];
+local q_running_key = KEYS[18];
+local q_lock_tokens_key = KEYS[19];
+local q_done_at_key = KEYS[20];
+
+-- ARGV
+local mid = ARGV[1];
+local result_arg = ARGV[2];
+local result_ttl_ms = tonumber(ARGV[3]);
+local now = tonumber(ARGV[4]);
+local token_arg = ARGV[5];
+
+-- Keys of the common helpers
+q.unique_key = q_unique_key;
+q.unique_mids_key = q_unique_mids_key;
+q.priorities_key = q_priorities_key;
+q.mids_ready_key = q_mids_ready_key;
+q.mids_ready_high_key = q_mids_ready_high_key;
+q.mids_ready_low_key = q_mids_ready_low_key;
+q.isleep_a_key = q_isleep_a_key;
+q.isleep_b_key = q_isleep_b_key;
+q.mid_circle_key = q_mid_circle_key;
+q.batches_key = q_batches_key;
+q.batches_done_key = q_batches_done_key;
+q.batch_mids_key = q_batch_mids_key;
+q.waiting_key = q_waiting_key;
+q.messages_key = q_messages_key;
+
+--------------------------------------------------------------------------------
+
+-- Dependents whose last parent this was become ready
+local release_dependents = function (mid)
+    local dependents = redis.call('hget', q_dependents_key, mid);
+    if (not dependents) then
+        return;
+    end
+    redis.call('hdel', q_dependents_key, mid);
+
+    for child in string.gmatch(dependents, '[^,]+') do
+        if (redis.call('hexists', q_waiting_key, child) == 1 and
+            redis.call('hincrby', q_waiting_key, child, -1) <= 0) then
+            redis.call('hdel', q_waiting_key, child);
+            if (redis.call('hexists', q_messages_key, child) == 1) then
+                push_ready(child);
+            end
+        end
+    end
+end
+
+-- Handed to another worker by a later dequeue, which finishes it instead
+if (redis.call('hget', q_lock_tokens_key, mid) ~= token_arg) then
+    return -1;
+end
+
+local added = redis.call('sadd', q_done_key, mid);
+
+-- Known as done past the gc of the mid, as long as results
+redis.call('zadd', q_done_at_key, now, mid);
+redis.call('zremrangebyscore', q_done_at_key, '-inf', now - result_ttl_ms);
+release_unique(mid);
+
+-- A freed slot lets a held back mid of the job type run
+if (redis.call('srem', q_running_key, mid) == 1) then
+    interrupt_sleep();
+end
+
+-- Outlives the GC of the mid, until the ttl. A `null` output, as of `()`,
+-- leaves nothing to fetch.
+if (result_arg ~= 'null') then
+    redis.call('set', q_result_key, result_arg, 'PX', result_ttl_ms);
+end
+
+release_dependents(mid);
+settle_batch(mid, 'succeeded', now, result_ttl_ms);
+
+wake_ready();
+
+return added;
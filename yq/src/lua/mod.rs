@@ -1,5 +1,32 @@
-pub(crate) const ENQUEUE: &str = include_str!("enqueue.lua");
-pub(crate) const DEQUEUE: &str = include_str!("dequeue.lua");
+// Scripts using the helpers of common.lua have it prepended
+pub(crate) const ENQUEUE: &str = concat!(include_str!("common.lua"), include_str!("enqueue.lua"));
+pub(crate) const ENQUEUE_MANY: &str =
+    concat!(include_str!("common.lua"), include_str!("enqueue_many.lua"));
+pub(crate) const DEQUEUE: &str = concat!(include_str!("common.lua"), include_str!("dequeue.lua"));
+pub(crate) const FINISH: &str = concat!(include_str!("common.lua"), include_str!("finish.lua"));
+pub(crate) const FAIL: &str = concat!(include_str!("common.lua"), include_str!("fail.lua"));
+pub(crate) const EXTEND_LOCK: &str = include_str!("extend_lock.lua");
+pub(crate) const RELEASE: &str = concat!(include_str!("common.lua"), include_str!("release.lua"));
 
-pub(crate) const ENQUEUE_AT: &str = include_str!("enqueue_at.lua");
-pub(crate) const DEQUEUE_AT: &str = include_str!("dequeue_at.lua");
+pub(crate) const STATUS: &str = include_str!("status.lua");
+pub(crate) const CANCEL: &str = concat!(include_str!("common.lua"), include_str!("cancel.lua"));
+
+pub(crate) const DEAD_LIST: &str = include_str!("dead_list.lua");
+pub(crate) const DEAD_REQUEUE: &str =
+    concat!(include_str!("common.lua"), include_str!("dead_requeue.lua"));
+pub(crate) const DEAD_PURGE: &str = include_str!("dead_purge.lua");
+
+pub(crate) const BATCH_CREATE: &str = include_str!("batch_create.lua");
+pub(crate) const BATCH_SEAL: &str =
+    concat!(include_str!("common.lua"), include_str!("batch_seal.lua"));
+pub(crate) const BATCH_STATUS: &str = include_str!("batch_status.lua");
+
+pub(crate) const PERIODIC_CLAIM: &str = concat!(
+    include_str!("common.lua"),
+    include_str!("periodic_claim.lua")
+);
+
+pub(crate) const ENQUEUE_AT: &str =
+    concat!(include_str!("common.lua"), include_str!("enqueue_at.lua"));
+pub(crate) const DEQUEUE_AT: &str =
+    concat!(include_str!("common.lua"), include_str!("dequeue_at.lua"));
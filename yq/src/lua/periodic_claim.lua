@@ -0,0 +1,59 @@
+-- KEYS
+local q_periodic_key = KEYS[1];
+local q_periodic_next_key = KEYS[2];
+local q_mid_seq_key = KEYS[3];
+local q_messages_key = KEYS[4];
+local q_schedule_key = KEYS[5];
+local q_done_key = KEYS[6];
+local q_unique_key = KEYS[7];
+local q_unique_mids_key = KEYS[8];
+local q_priorities_key = KEYS[9];
+local q_lock_times_key = KEYS[10];
+local q_timeouts_key = KEYS[11];
+
+-- ARGV
+local name = ARGV[1];
+local run_at = tonumber(ARGV[2]);
+local next_run_at = tonumber(ARGV[3]);
+-- The job of the occurrence, as in enqueue_at.lua
+local mcnt_arg = ARGV[4];
+local unique_key_arg = ARGV[5];
+local priority_arg = ARGV[6];
+local lock_ms = tonumber(ARGV[7]);
+local timeout_ms = tonumber(ARGV[8]);
+
+-- Keys of the common helpers
+q.mid_seq_key = q_mid_seq_key;
+q.messages_key = q_messages_key;
+q.schedule_key = q_schedule_key;
+q.done_key = q_done_key;
+q.unique_key = q_unique_key;
+q.unique_mids_key = q_unique_mids_key;
+q.priorities_key = q_priorities_key;
+q.lock_times_key = q_lock_times_key;
+q.timeouts_key = q_timeouts_key;
+
+--------------------------------------------------------------------------------
+-- Return {'added'|'exists', mid} when this caller owns the occurrence at
+-- run_at, which is scheduled along with the claim, {'lost'} otherwise.
+-- Another scheduler that already advanced the name, or a removed name, loses.
+-- Without a job the name is only advanced, {'advanced'}.
+
+if (redis.call('hexists', q_periodic_key, name) == 0) then
+    redis.call('zrem', q_periodic_next_key, name);
+    return {'lost'};
+end
+
+local current = tonumber(redis.call('zscore', q_periodic_next_key, name));
+if (current ~= run_at) then
+    return {'lost'};
+end
+
+redis.call('zadd', q_periodic_next_key, next_run_at, name);
+
+-- A job that cannot run, or re-registered since the caller found it removed
+if (mcnt_arg == '') then
+    return {'advanced'};
+end
+
+return schedule_message(mcnt_arg, run_at, unique_key_arg, priority_arg, lock_ms, timeout_ms, '', '');
@@ -0,0 +1,48 @@
+-- KEYS
+local q_messages_key = KEYS[1];
+local q_locks_key = KEYS[2];
+local q_attempts_key = KEYS[3];
+local q_done_key = KEYS[4];
+local q_running_key = KEYS[5];
+local q_isleep_a_key = KEYS[6];
+local q_isleep_b_key = KEYS[7];
+local q_lock_tokens_key = KEYS[8];
+
+-- ARGV
+local mid = ARGV[1];
+local now_i = tonumber(ARGV[2]);
+local started = ARGV[3]; -- '1' once the handler ran
+local token_arg = ARGV[4];
+
+-- Keys of the common helpers
+q.isleep_a_key = q_isleep_a_key;
+q.isleep_b_key = q_isleep_b_key;
+
+--------------------------------------------------------------------------------
+
+if (redis.call('hexists', q_messages_key, mid) == 0 or
+    redis.call('sismember', q_done_key, mid) == 1) then
+    return {'missing'};
+end
+
+-- Handed to another worker by a later dequeue
+if (redis.call('hget', q_lock_tokens_key, mid) ~= token_arg) then
+    return {'lost'};
+end
+
+-- Once expired the mid may already be handed to another worker
+local exp_lock = tonumber(redis.call('hget', q_locks_key, mid)) or 0;
+if (now_i >= exp_lock) then
+    return {'expired'};
+end
+
+-- The mid is still in the circle, queued again once unlocked
+redis.call('hdel', q_locks_key, mid);
+redis.call('hdel', q_lock_tokens_key, mid);
+redis.call('srem', q_running_key, mid);
+if (started == '0') then
+    redis.call('hincrby', q_attempts_key, mid, -1);
+end
+interrupt_sleep();
+
+return {'released'};
@@ -0,0 +1,57 @@
+-- KEYS
+local q_messages_key = KEYS[1];
+local q_locks_key = KEYS[2];
+local q_done_key = KEYS[3];
+local q_err_key = KEYS[4];
+local q_schedule_key = KEYS[5];
+local q_dead_key = KEYS[6];
+local q_waiting_key = KEYS[7];
+local q_cancelled_key = KEYS[8];
+local q_done_at_key = KEYS[9];
+
+-- ARGV
+local mid = ARGV[1];
+local now_i = tonumber(ARGV[2]);
+
+--------------------------------------------------------------------------------
+
+if (redis.call('zscore', q_dead_key, mid)) then
+    return {'dead'};
+end
+
+if (redis.call('zscore', q_cancelled_key, mid)) then
+    return {'cancelled'};
+end
+
+if (redis.call('hexists', q_messages_key, mid) == 0) then
+    -- Done and already gc'd
+    if (redis.call('zscore', q_done_at_key, mid)) then
+        return {'done'};
+    end
+    return {'missing'};
+end
+
+if (redis.call('sismember', q_done_key, mid) == 1) then
+    return {'done'};
+end
+
+local run_at = redis.call('zscore', q_schedule_key, mid);
+if (run_at) then
+    local err = redis.call('hget', q_err_key, mid);
+    if (err) then
+        return {'failed', err, tonumber(run_at)};
+    end
+    return {'scheduled', tonumber(run_at)};
+end
+
+local exp_lock = tonumber(redis.call('hget', q_locks_key, mid)) or 0;
+if (now_i < exp_lock) then
+    return {'locked', exp_lock};
+end
+
+local pending = tonumber(redis.call('hget', q_waiting_key, mid));
+if (pending) then
+    return {'waiting', pending};
+end
+
+return {'queued'};
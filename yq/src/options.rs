@@ -0,0 +1,41 @@
+use crate::Priority;
+use std::time::Duration;
+
+#[derive(Clone, Debug, Default)]
+pub struct EnqueueOptions {
+    pub(crate) unique_key: Option<String>,
+    pub(crate) priority: Option<Priority>,
+    pub(crate) depends_on: Vec<i64>,
+    pub(crate) timeout: Option<Duration>,
+}
+
+impl EnqueueOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Overrides `Job::unique_key`
+    pub fn unique_key(mut self, unique_key: impl Into<String>) -> Self {
+        self.unique_key = Some(unique_key.into());
+        self
+    }
+
+    // Overrides `Job::PRIORITY`
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    // Overrides `Job::TIMEOUT`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    // Ready only once every parent mid is done, cancelled when one dies or is
+    // cancelled. Not supported for scheduled or batch enqueues.
+    pub fn depends_on(mut self, parents: impl IntoIterator<Item = i64>) -> Self {
+        self.depends_on.extend(parents);
+        self
+    }
+}
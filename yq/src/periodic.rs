@@ -0,0 +1,367 @@
+use crate::helper::{duration_ms_arg, read_redis_value_as_int, read_redis_value_as_str};
+use crate::{EnqueueOptions, Job, PreparedJob, Queue, YqError, YqResult};
+use chrono::TimeZone;
+use redis::{FromRedisValue, RedisResult, Script, ScriptInvocation};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Recurrence {
+    // Cron expression with a seconds field, in UTC - "0 */5 * * * *"
+    Cron(String),
+    Every(Duration),
+}
+
+impl Recurrence {
+    // First occurrence strictly after `after` (unix milliseconds)
+    pub fn next_after(&self, after: i64) -> YqResult<i64> {
+        match self {
+            Recurrence::Cron(expr) => {
+                let schedule = cron::Schedule::from_str(expr)
+                    .map_err(|err| YqError::InvalidPeriodic(format!("{expr}: {err}")))?;
+                let after = chrono::Utc
+                    .timestamp_millis_opt(after)
+                    .single()
+                    .ok_or_else(|| YqError::InvalidPeriodic(format!("invalid time: {after}")))?;
+
+                schedule
+                    .after(&after)
+                    .next()
+                    .map(|next| next.timestamp_millis())
+                    .ok_or_else(|| YqError::InvalidPeriodic(format!("{expr}: no next occurrence")))
+            }
+            Recurrence::Every(interval) => Ok(after + Self::interval_ms(interval)?),
+        }
+    }
+
+    // Next occurrence once `run_at` has been materialized at `now`. Occurrences
+    // missed while no scheduler was running are skipped, not caught up.
+    pub fn next_due(&self, run_at: i64, now: i64) -> YqResult<i64> {
+        match self {
+            Recurrence::Cron(_) => self.next_after(now.max(run_at)),
+            Recurrence::Every(interval) => {
+                let ms = Self::interval_ms(interval)?;
+                let missed = (now - run_at).max(0) / ms;
+                Ok(run_at + (missed + 1) * ms)
+            }
+        }
+    }
+
+    fn interval_ms(interval: &Duration) -> YqResult<i64> {
+        match interval.as_millis() {
+            0 => Err(YqError::InvalidPeriodic(format!(
+                "interval shorter than a millisecond: {interval:?}"
+            ))),
+            ms => Ok(ms as i64),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PeriodicJob {
+    pub name: String,
+    pub recurrence: Recurrence,
+    pub(crate) job: PreparedJob,
+}
+
+impl PeriodicJob {
+    pub fn new<J: Job>(name: impl Into<String>, recurrence: Recurrence, job: &J) -> YqResult<Self> {
+        Self::with_options(name, recurrence, job, &EnqueueOptions::default())
+    }
+
+    pub fn with_options<J: Job>(
+        name: impl Into<String>,
+        recurrence: Recurrence,
+        job: &J,
+        options: &EnqueueOptions,
+    ) -> YqResult<Self> {
+        let name = name.into();
+        if name.is_empty() {
+            return Err(YqError::InvalidPeriodic("name is empty".into()));
+        }
+
+        // Reject a bad cron expression or interval before it reaches redis
+        recurrence.next_after(0)?;
+
+        Ok(Self {
+            name,
+            recurrence,
+            job: PreparedJob::with_options(job, options)?,
+        })
+    }
+
+    pub fn job(&self) -> &PreparedJob {
+        &self.job
+    }
+}
+
+#[derive(Clone)]
+pub struct PeriodicAction {
+    claim_script: Script,
+    queue: Queue,
+}
+
+impl PeriodicAction {
+    pub fn new(queue: Queue) -> Self {
+        Self {
+            claim_script: Script::new(crate::lua::PERIODIC_CLAIM),
+            queue,
+        }
+    }
+
+    // Replaces a job of the same name, its next occurrence is recomputed
+    pub fn prepare_register(&self, periodic: &PeriodicJob, now: i64) -> YqResult<redis::Pipeline> {
+        let definition = serde_json::to_string(periodic).map_err(YqError::SerializeJob)?;
+        let next_run_at = periodic.recurrence.next_after(now)?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .hset(self.queue.periodic_key.as_str(), &periodic.name, definition)
+            .ignore()
+            .zadd(
+                self.queue.periodic_next_key.as_str(),
+                &periodic.name,
+                next_run_at,
+            )
+            .ignore();
+
+        Ok(pipe)
+    }
+
+    // Invocation returns the number of removed definitions
+    pub fn prepare_remove(&self, name: &str) -> redis::Pipeline {
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .hdel(self.queue.periodic_key.as_str(), name)
+            .zrem(self.queue.periodic_next_key.as_str(), name)
+            .ignore();
+
+        pipe
+    }
+
+    pub fn prepare_list(&self) -> redis::Cmd {
+        redis::Cmd::hvals(self.queue.periodic_key.as_str())
+    }
+
+    pub fn prepare_get(&self, name: &str) -> redis::Cmd {
+        redis::Cmd::hget(self.queue.periodic_key.as_str(), name)
+    }
+
+    // Invocation returns {(name, run-at)} due at `now`
+    pub fn prepare_due(&self, now: i64) -> redis::Cmd {
+        redis::Cmd::zrangebyscore_withscores(self.queue.periodic_next_key.as_str(), "-inf", now)
+    }
+
+    // Invocation returns the earliest {(name, run-at)}, if any
+    pub fn prepare_next(&self) -> redis::Cmd {
+        redis::Cmd::zrange_withscores(self.queue.periodic_next_key.as_str(), 0, 0)
+    }
+
+    // The occurrence at `run_at` is claimed and its `job` scheduled at once, so
+    // it is neither lost nor doubled. Without a `job` a removed name is dropped
+    // and one still registered only advanced to `next_run_at`.
+    pub fn prepare_claim(
+        &self,
+        name: &str,
+        run_at: i64,
+        next_run_at: i64,
+        job: Option<&PreparedJob>,
+    ) -> ScriptInvocation<'_> {
+        let mut invoke = self.claim_script.prepare_invoke();
+        invoke
+            .key(self.queue.periodic_key.as_str())
+            .key(self.queue.periodic_next_key.as_str())
+            .key(self.queue.mid_seq_key.as_str())
+            .key(self.queue.messages_key.as_str())
+            .key(self.queue.schedule_key.as_str())
+            .key(self.queue.done_key.as_str())
+            .key(self.queue.unique_key.as_str())
+            .key(self.queue.unique_mids_key.as_str())
+            .key(self.queue.priorities_key.as_str())
+            .key(self.queue.lock_times_key.as_str())
+            .key(self.queue.timeouts_key.as_str());
+
+        invoke.arg(name).arg(run_at).arg(next_run_at);
+
+        match job {
+            Some(job) => invoke
+                .arg(&job.job_data)
+                .arg(job.unique_key.as_deref().unwrap_or_default())
+                .arg(job.priority.as_str())
+                .arg(duration_ms_arg(job.lock))
+                .arg(duration_ms_arg(job.timeout)),
+            None => invoke.arg("").arg("").arg("normal").arg(-1).arg(-1),
+        };
+
+        invoke
+    }
+}
+
+#[derive(Debug)]
+pub enum PeriodicClaimStatus {
+    // Claimed, the occurrence was scheduled as the mid
+    Added(i64),
+    // Claimed, an equal job is still queued, scheduled or running
+    Exists(i64),
+    // Claimed without a job, only advanced to the next run
+    Advanced,
+    // Claimed by another scheduler, or the name was removed
+    Lost,
+    Unknown(String),
+}
+
+impl TryFrom<&[redis::Value]> for PeriodicClaimStatus {
+    type Error = redis::RedisError;
+
+    fn try_from(values: &[redis::Value]) -> Result<Self, Self::Error> {
+        let mut iter = values.iter();
+        let action = read_redis_value_as_str(
+            iter.next(),
+            "invalid periodic claim status - invalid action",
+        )?;
+
+        let status = match action.as_ref() {
+            "added" => {
+                let mid = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid periodic claim status - invalid mid",
+                )?;
+                PeriodicClaimStatus::Added(mid)
+            }
+            "exists" => {
+                let mid = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid periodic claim status - invalid mid",
+                )?;
+                PeriodicClaimStatus::Exists(mid)
+            }
+            "advanced" => PeriodicClaimStatus::Advanced,
+            "lost" => PeriodicClaimStatus::Lost,
+            _ => PeriodicClaimStatus::Unknown(format!("{values:?}")),
+        };
+
+        Ok(status)
+    }
+}
+
+impl FromRedisValue for PeriodicClaimStatus {
+    fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+        match v {
+            redis::Value::Bulk(bulk) => PeriodicClaimStatus::try_from(bulk.as_slice()),
+            _ => Err(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "invalid periodic claim status - invalid value type",
+                format!("{v:?}"),
+            ))),
+        }
+    }
+}
+
+impl FromRedisValue for PeriodicJob {
+    fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+        let definition = read_redis_value_as_str(Some(v), "invalid periodic job - invalid value")?;
+        serde_json::from_str(&definition).map_err(|err| {
+            redis::RedisError::from((
+                redis::ErrorKind::TypeError,
+                "invalid periodic job - invalid json",
+                err.to_string(),
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: i64 = 60_000;
+
+    fn at(hour: u32, min: u32, sec: u32) -> i64 {
+        chrono::Utc
+            .with_ymd_and_hms(2024, 1, 1, hour, min, sec)
+            .unwrap()
+            .timestamp_millis()
+    }
+
+    #[test]
+    fn cron_next_due_on_time() {
+        let recurrence = Recurrence::Cron("0 */5 * * * *".into());
+        assert_eq!(
+            recurrence.next_due(at(10, 0, 0), at(10, 0, 0)).unwrap(),
+            at(10, 5, 0)
+        );
+    }
+
+    #[test]
+    fn cron_next_due_skips_missed() {
+        let recurrence = Recurrence::Cron("0 */5 * * * *".into());
+        assert_eq!(
+            recurrence.next_due(at(10, 0, 0), at(10, 17, 30)).unwrap(),
+            at(10, 20, 0)
+        );
+        // On an occurrence, the next one is strictly after
+        assert_eq!(
+            recurrence.next_due(at(10, 0, 0), at(10, 20, 0)).unwrap(),
+            at(10, 25, 0)
+        );
+    }
+
+    #[test]
+    fn interval_next_due_on_time() {
+        let recurrence = Recurrence::Every(Duration::from_secs(60));
+        assert_eq!(
+            recurrence.next_due(10 * MINUTE, 10 * MINUTE).unwrap(),
+            11 * MINUTE
+        );
+        assert_eq!(
+            recurrence
+                .next_due(10 * MINUTE, 10 * MINUTE + 59_999)
+                .unwrap(),
+            11 * MINUTE
+        );
+    }
+
+    #[test]
+    fn interval_next_due_skips_missed() {
+        let recurrence = Recurrence::Every(Duration::from_secs(60));
+        // Down for 2.5 intervals, stays on the grid of run_at
+        assert_eq!(
+            recurrence
+                .next_due(10 * MINUTE, 12 * MINUTE + 30_000)
+                .unwrap(),
+            13 * MINUTE
+        );
+        assert_eq!(
+            recurrence.next_due(10 * MINUTE, 12 * MINUTE).unwrap(),
+            13 * MINUTE
+        );
+    }
+
+    #[test]
+    fn cron_next_due_past_last_occurrence() {
+        let recurrence = Recurrence::Cron("0 0 0 1 1 * 2000".into());
+        let y2k = chrono::Utc
+            .with_ymd_and_hms(2000, 1, 1, 0, 0, 0)
+            .unwrap()
+            .timestamp_millis();
+        assert_eq!(recurrence.next_after(y2k - 1000).unwrap(), y2k);
+
+        let err = recurrence.next_due(at(10, 0, 0), at(10, 0, 0)).unwrap_err();
+        assert!(matches!(err, YqError::InvalidPeriodic(_)), "{err:?}");
+    }
+
+    #[test]
+    fn rejects_bad_recurrence() {
+        let err = Recurrence::Every(Duration::from_micros(10))
+            .next_after(0)
+            .unwrap_err();
+        assert!(matches!(err, YqError::InvalidPeriodic(_)), "{err:?}");
+
+        let err = Recurrence::Cron("every minute".into())
+            .next_after(0)
+            .unwrap_err();
+        assert!(matches!(err, YqError::InvalidPeriodic(_)), "{err:?}");
+    }
+}
@@ -1,27 +1,58 @@
-use crate::{redis_keys, ArcString};
+use crate::{redis_keys, ArcString, YqError, YqResult};
 use std::sync::Arc;
+use std::time::Duration;
 
 const DEFAULT_PREFIX: &str = "yq";
 const DEFAULT_QUEUE: &str = "0";
-const DEFAULT_LOCK_MS: i64 = 60 * 60; // 60 minutes
+const DEFAULT_LOCK: Duration = Duration::from_secs(60 * 60); // 60 minutes
+const DEFAULT_RESULT_TTL: Duration = Duration::from_secs(24 * 60 * 60); // 1 day
+const MAX_NAME_LEN: usize = 64;
 
 #[derive(Clone)]
 pub struct Queue {
+    pub prefix: ArcString,
     pub queue_name: ArcString,
-    pub(crate) default_lock_ms: i64,
+    pub(crate) default_lock: Duration,
+    pub(crate) result_ttl: Duration,
     pub(crate) mid_seq_key: ArcString,
     pub(crate) messages_key: ArcString,
     pub(crate) lock_times_key: ArcString,
+    pub(crate) timeouts_key: ArcString,
     pub(crate) locks_key: ArcString,
+    pub(crate) lock_seq_key: ArcString,
+    pub(crate) lock_tokens_key: ArcString,
     pub(crate) done_key: ArcString,
+    pub(crate) done_at_key: ArcString,
+    pub(crate) attempts_key: ArcString,
     pub err_messages_key: ArcString,
     pub err_key: ArcString,
+    pub(crate) first_failed_key: ArcString,
     pub(crate) mids_ready_key: ArcString,
+    pub(crate) mids_ready_high_key: ArcString,
+    pub(crate) mids_ready_low_key: ArcString,
+    pub(crate) priorities_key: ArcString,
+    pub(crate) circle_turn_key: ArcString,
     pub(crate) mid_circle_key: ArcString,
     pub(crate) ndry_runs_key: ArcString,
     pub(crate) isleep_a_key: ArcString,
     pub(crate) isleep_b_key: ArcString,
     pub(crate) schedule_key: ArcString,
+    pub(crate) legacy_schedule_key: ArcString,
+    pub(crate) dead_key: ArcString,
+    pub(crate) dead_messages_key: ArcString,
+    pub(crate) dead_errors_key: ArcString,
+    pub(crate) dead_attempts_key: ArcString,
+    pub(crate) unique_key: ArcString,
+    pub(crate) unique_mids_key: ArcString,
+    pub(crate) periodic_key: ArcString,
+    pub(crate) periodic_next_key: ArcString,
+    pub(crate) waiting_key: ArcString,
+    pub(crate) dependents_key: ArcString,
+    pub(crate) cancelled_key: ArcString,
+    pub(crate) batch_seq_key: ArcString,
+    pub(crate) batches_key: ArcString,
+    pub(crate) batches_done_key: ArcString,
+    pub(crate) batch_mids_key: ArcString,
 }
 
 impl Default for Queue {
@@ -29,42 +60,217 @@ impl Default for Queue {
         Queue::new(
             Arc::new(DEFAULT_PREFIX.into()),
             Arc::new(DEFAULT_QUEUE.into()),
+            DEFAULT_LOCK,
+            DEFAULT_RESULT_TTL,
         )
     }
 }
 
 impl Queue {
-    pub(crate) fn new(prefix: ArcString, queue_name: ArcString) -> Self {
+    pub fn builder() -> QueueBuilder {
+        QueueBuilder::default()
+    }
+
+    pub(crate) fn new(
+        prefix: ArcString,
+        queue_name: ArcString,
+        default_lock: Duration,
+        result_ttl: Duration,
+    ) -> Self {
         let mid_seq_key = redis_keys::mid_seq_key(&prefix, &queue_name);
         let messages_key = redis_keys::messages_key(&prefix, &queue_name);
         let lock_times_key = redis_keys::lock_times_key(&prefix, &queue_name);
+        let timeouts_key = redis_keys::timeouts_key(&prefix, &queue_name);
         let locks_key = redis_keys::locks_key(&prefix, &queue_name);
+        let lock_seq_key = redis_keys::lock_seq_key(&prefix, &queue_name);
+        let lock_tokens_key = redis_keys::lock_tokens_key(&prefix, &queue_name);
         let err_messages_key = redis_keys::err_messages_key(&prefix, &queue_name);
         let err_key = redis_keys::err_key(&prefix, &queue_name);
+        let first_failed_key = redis_keys::first_failed_key(&prefix, &queue_name);
         let done_key = redis_keys::done_key(&prefix, &queue_name);
+        let done_at_key = redis_keys::done_at_key(&prefix, &queue_name);
+        let attempts_key = redis_keys::attempts_key(&prefix, &queue_name);
         let mids_ready_key = redis_keys::mids_ready_key(&prefix, &queue_name);
+        let mids_ready_high_key = redis_keys::mids_ready_high_key(&prefix, &queue_name);
+        let mids_ready_low_key = redis_keys::mids_ready_low_key(&prefix, &queue_name);
+        let priorities_key = redis_keys::priorities_key(&prefix, &queue_name);
+        let circle_turn_key = redis_keys::circle_turn_key(&prefix, &queue_name);
         let mid_circle_key = redis_keys::mid_circle_key(&prefix, &queue_name);
         let ndry_runs_key = redis_keys::ndry_runs_key(&prefix, &queue_name);
         let isleep_a_key = redis_keys::isleep_a_key(&prefix, &queue_name);
         let isleep_b_key = redis_keys::isleep_b_key(&prefix, &queue_name);
-        let schedule_key = redis_keys::schedule_key(&prefix);
+        let schedule_key = redis_keys::schedule_key(&prefix, &queue_name);
+        let legacy_schedule_key = redis_keys::legacy_schedule_key(&prefix);
+        let dead_key = redis_keys::dead_key(&prefix, &queue_name);
+        let dead_messages_key = redis_keys::dead_messages_key(&prefix, &queue_name);
+        let dead_errors_key = redis_keys::dead_errors_key(&prefix, &queue_name);
+        let dead_attempts_key = redis_keys::dead_attempts_key(&prefix, &queue_name);
+        let unique_key = redis_keys::unique_key(&prefix, &queue_name);
+        let unique_mids_key = redis_keys::unique_mids_key(&prefix, &queue_name);
+        let periodic_key = redis_keys::periodic_key(&prefix, &queue_name);
+        let periodic_next_key = redis_keys::periodic_next_key(&prefix, &queue_name);
+        let waiting_key = redis_keys::waiting_key(&prefix, &queue_name);
+        let dependents_key = redis_keys::dependents_key(&prefix, &queue_name);
+        let cancelled_key = redis_keys::cancelled_key(&prefix, &queue_name);
+        let batch_seq_key = redis_keys::batch_seq_key(&prefix, &queue_name);
+        let batches_key = redis_keys::batches_key(&prefix, &queue_name);
+        let batches_done_key = redis_keys::batches_done_key(&prefix, &queue_name);
+        let batch_mids_key = redis_keys::batch_mids_key(&prefix, &queue_name);
 
         Self {
+            prefix,
             queue_name,
-            default_lock_ms: DEFAULT_LOCK_MS,
+            default_lock,
+            result_ttl,
             mid_seq_key,
             messages_key,
             lock_times_key,
+            timeouts_key,
             locks_key,
+            lock_seq_key,
+            lock_tokens_key,
             done_key,
+            done_at_key,
+            attempts_key,
             err_messages_key,
             err_key,
+            first_failed_key,
             mids_ready_key,
+            mids_ready_high_key,
+            mids_ready_low_key,
+            priorities_key,
+            circle_turn_key,
             mid_circle_key,
             ndry_runs_key,
             isleep_a_key,
             isleep_b_key,
             schedule_key,
+            legacy_schedule_key,
+            dead_key,
+            dead_messages_key,
+            dead_errors_key,
+            dead_attempts_key,
+            unique_key,
+            unique_mids_key,
+            periodic_key,
+            periodic_next_key,
+            waiting_key,
+            dependents_key,
+            cancelled_key,
+            batch_seq_key,
+            batches_key,
+            batches_done_key,
+            batch_mids_key,
+        }
+    }
+
+    pub(crate) fn result_key(&self, mid: i64) -> String {
+        redis_keys::result_key(&self.prefix, &self.queue_name, mid)
+    }
+
+    pub(crate) fn rate_limit_key(&self, job_type: &str) -> String {
+        redis_keys::rate_limit_key(&self.prefix, &self.queue_name, job_type)
+    }
+
+    pub(crate) fn rate_deferred_key(&self, job_type: &str) -> String {
+        redis_keys::rate_deferred_key(&self.prefix, &self.queue_name, job_type)
+    }
+
+    pub(crate) fn concurrency_key(&self, job_type: &str) -> String {
+        redis_keys::concurrency_key(&self.prefix, &self.queue_name, job_type)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct QueueBuilder {
+    prefix: String,
+    queue_name: String,
+    default_lock: Duration,
+    result_ttl: Duration,
+}
+
+impl Default for QueueBuilder {
+    fn default() -> Self {
+        Self {
+            prefix: DEFAULT_PREFIX.into(),
+            queue_name: DEFAULT_QUEUE.into(),
+            default_lock: DEFAULT_LOCK,
+            result_ttl: DEFAULT_RESULT_TTL,
+        }
+    }
+}
+
+impl QueueBuilder {
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn queue_name(mut self, queue_name: impl Into<String>) -> Self {
+        self.queue_name = queue_name.into();
+        self
+    }
+
+    // Lock of jobs without `Job::LOCK`
+    pub fn default_lock(mut self, default_lock: Duration) -> Self {
+        self.default_lock = default_lock;
+        self
+    }
+
+    // How long the output of a done job can be fetched
+    pub fn result_ttl(mut self, result_ttl: Duration) -> Self {
+        self.result_ttl = result_ttl;
+        self
+    }
+
+    pub fn build(self) -> YqResult<Queue> {
+        validate_name("prefix", &self.prefix)?;
+        validate_name("queue_name", &self.queue_name)?;
+
+        if self.default_lock.as_millis() == 0 {
+            return Err(YqError::InvalidQueue(format!(
+                "default_lock shorter than a millisecond: {:?}",
+                self.default_lock
+            )));
         }
+
+        if self.result_ttl.as_millis() == 0 {
+            return Err(YqError::InvalidQueue(format!(
+                "result_ttl shorter than a millisecond: {:?}",
+                self.result_ttl
+            )));
+        }
+
+        Ok(Queue::new(
+            Arc::new(self.prefix),
+            Arc::new(self.queue_name),
+            self.default_lock,
+            self.result_ttl,
+        ))
+    }
+}
+
+// Names become part of redis keys ({prefix}:{queue_name}:...), so keep them
+// free of separators, hash tags and whitespace.
+fn validate_name(field: &str, name: &str) -> YqResult<()> {
+    if name.is_empty() {
+        return Err(YqError::InvalidQueue(format!("{field} is empty")));
     }
+
+    if name.len() > MAX_NAME_LEN {
+        return Err(YqError::InvalidQueue(format!(
+            "{field} is longer than {MAX_NAME_LEN}: {name}"
+        )));
+    }
+
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+    {
+        return Err(YqError::InvalidQueue(format!(
+            "{field} contains invalid char {c:?}: {name}"
+        )));
+    }
+
+    Ok(())
 }
@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+// At most `limit` mids of a job type handed out per sliding `period`. Mids over
+// the limit are deferred to the schedule, `limit` of them per later period, and
+// moved back by yq-scheduler once due. Deferred mids never run unless a
+// yq-scheduler serves the queue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    limit: u32,
+    period: Duration,
+}
+
+impl RateLimit {
+    // Panics on a zero limit or a period under 1ms, at compile time for a
+    // `Job::RATE_LIMIT`
+    pub const fn new(limit: u32, period: Duration) -> Self {
+        assert!(limit > 0, "rate limit must be above zero");
+        assert!(
+            period.as_millis() >= 1,
+            "rate limit period must be at least 1ms"
+        );
+        Self { limit, period }
+    }
+
+    pub const fn per_second(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(1))
+    }
+
+    pub const fn per_minute(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(60))
+    }
+
+    pub const fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    pub const fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "rate limit must be above zero")]
+    fn rejects_zero_limit() {
+        RateLimit::per_second(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "rate limit period must be at least 1ms")]
+    fn rejects_period_under_a_millisecond() {
+        RateLimit::new(1, Duration::from_micros(999));
+    }
+}
@@ -18,36 +18,158 @@ pub(crate) fn lock_times_key(prefix: &str, queue_name: &str) -> ArcString {
     format!("{prefix}:{queue_name}:lock-times").into()
 }
 
+// timeouts      - hash: {mid timeout-ms} ; Optional mid-specific execution timeout
+#[inline]
+pub(crate) fn timeouts_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:timeouts").into()
+}
+
 // locks         - hash: {mid    lock-expiry-time} ; Active locks
 #[inline]
 pub(crate) fn locks_key(prefix: &str, queue_name: &str) -> ArcString {
     format!("{prefix}:{queue_name}:locks").into()
 }
 
-// messages      - hash: {mid mcontent} ; Message content
+// lock-seq      - int
+#[inline]
+pub(crate) fn lock_seq_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:lock-seq").into()
+}
+
+// lock-tokens   - hash: {mid token}    ; Token of the dequeue holding the lock
+#[inline]
+pub(crate) fn lock_tokens_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:lock-tokens").into()
+}
+
+// attempts      - hash: {mid n}        ; Times the mid has been handed out
+#[inline]
+pub(crate) fn attempts_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:attempts").into()
+}
+
+// err-msgs      - hash: {mid job-data} ; Payload of failed mids
 #[inline]
 pub(crate) fn err_messages_key(prefix: &str, queue_name: &str) -> ArcString {
     format!("{prefix}:{queue_name}:err-msgs").into()
 }
 
-// error          - mid set: awaiting gc, etc.
+// err           - hash: {mid error}    ; Last error of failed mids
 #[inline]
 pub(crate) fn err_key(prefix: &str, queue_name: &str) -> ArcString {
     format!("{prefix}:{queue_name}:err").into()
 }
 
+// first-failed  - hash: {mid first-failed-at-ms} ; First failure of failed
+//                                                  mids, kept while dead
+#[inline]
+pub(crate) fn first_failed_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:first-failed").into()
+}
+
+// dead          - zset: {mid died-at-ms} ; Mids that exhausted their attempts
+#[inline]
+pub(crate) fn dead_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:dead").into()
+}
+
+// dead-msgs     - hash: {mid mcontent} ; Message content of dead mids
+#[inline]
+pub(crate) fn dead_messages_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:dead-msgs").into()
+}
+
+// dead-errs     - hash: {mid error}    ; Last error of dead mids
+#[inline]
+pub(crate) fn dead_errors_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:dead-errs").into()
+}
+
+// dead-attempts - hash: {mid n}        ; Attempts made before dying
+#[inline]
+pub(crate) fn dead_attempts_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:dead-attempts").into()
+}
+
+// waiting       - hash: {mid n}        ; Mids waiting on n unfinished parents
+#[inline]
+pub(crate) fn waiting_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:waiting").into()
+}
+
+// dependents    - hash: {mid "mid,mid"} ; Waiting children of a parent mid
+#[inline]
+pub(crate) fn dependents_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:dependents").into()
+}
+
+// cancelled     - zset: {mid cancelled-at-ms} ; Cancelled mids, kept as long as results
+#[inline]
+pub(crate) fn cancelled_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:cancelled").into()
+}
+
+// done-at       - zset: {mid done-at-ms} ; Done mids, kept past their gc as long as
+//                                          results
+#[inline]
+pub(crate) fn done_at_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:done-at").into()
+}
+
 // done          - mid set: awaiting gc, etc.
 #[inline]
 pub(crate) fn done_key(prefix: &str, queue_name: &str) -> ArcString {
     format!("{prefix}:{queue_name}:done").into()
 }
 
+// result:{mid}  - string: job-output ; Serialized output of a done mid, with ttl
+#[inline]
+pub(crate) fn result_key(prefix: &str, queue_name: &str, mid: i64) -> String {
+    format!("{prefix}:{queue_name}:result:{mid}")
+}
+
+// unique        - hash: {unique-key mid} ; Mid holding a uniqueness key
+#[inline]
+pub(crate) fn unique_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:unique").into()
+}
+
+// unique-mids   - hash: {mid unique-key} ; Reverse of unique, for release
+#[inline]
+pub(crate) fn unique_mids_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:unique-mids").into()
+}
+
 // mids-ready    - list: mids for immediate handling     (push to left, pop from right)
 #[inline]
 pub(crate) fn mids_ready_key(prefix: &str, queue_name: &str) -> ArcString {
     format!("{prefix}:{queue_name}:mids-ready").into()
 }
 
+// mids-ready-high - list: high priority mids, popped before mids-ready
+#[inline]
+pub(crate) fn mids_ready_high_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:mids-ready-high").into()
+}
+
+// mids-ready-low - list: low priority mids, popped after mids-ready
+#[inline]
+pub(crate) fn mids_ready_low_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:mids-ready-low").into()
+}
+
+// priorities    - hash: {mid priority} ; Non-normal priority of a mid
+#[inline]
+pub(crate) fn priorities_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:priorities").into()
+}
+
+// circle-turn   - int: ready pops since the circle was last served
+#[inline]
+pub(crate) fn circle_turn_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:circle-turn").into()
+}
+
 // mid-circle    - list: mids for maintenance processing (push to left, pop from right)
 #[inline]
 pub(crate) fn mid_circle_key(prefix: &str, queue_name: &str) -> ArcString {
@@ -72,8 +194,74 @@ pub(crate) fn isleep_b_key(prefix: &str, queue_name: &str) -> ArcString {
     format!("{prefix}:{queue_name}:isleep-b").into()
 }
 
-// isleep-b      - list: 0/1 sentinel element for `interruptible-sleep`
+// periodic      - hash: {name periodic-job} ; Recurring job definitions (json)
+#[inline]
+pub(crate) fn periodic_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:periodic").into()
+}
+
+// periodic-next - zset: {name run-at-ms} ; Next occurrence of each recurring job
+#[inline]
+pub(crate) fn periodic_next_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:periodic-next").into()
+}
+
+// batch-seq     - int
+#[inline]
+pub(crate) fn batch_seq_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:batch-seq").into()
+}
+
+// batches       - hash: {"id:field" n} ; pending, succeeded, failed, sealed and
+//                                        callback of each batch
+#[inline]
+pub(crate) fn batches_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:batches").into()
+}
+
+// batches-done  - zset: {id completed-at-ms} ; Completed batches, kept as long as results
+#[inline]
+pub(crate) fn batches_done_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:batches-done").into()
+}
+
+// batch-mids    - hash: {mid id}       ; Batch of the mids not yet settled
+#[inline]
+pub(crate) fn batch_mids_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:batch-mids").into()
+}
+
+// rate:{type}   - zset: {"mid:handed-out-ms" handed-out-ms} ; Sliding window of
+//                                                            a rate limited job type
+#[inline]
+pub(crate) fn rate_limit_key(prefix: &str, queue_name: &str, job_type: &str) -> String {
+    format!("{prefix}:{queue_name}:rate:{job_type}")
+}
+
+// rate-deferred:{type} - int: mids of a rate limited job type deferred to the
+//                         schedule, expires once the last is due
+#[inline]
+pub(crate) fn rate_deferred_key(prefix: &str, queue_name: &str, job_type: &str) -> String {
+    format!("{prefix}:{queue_name}:rate-deferred:{job_type}")
+}
+
+// running:{type} - mid set            ; Mids holding a slot of a concurrency
+//                                      limited job type
+#[inline]
+pub(crate) fn concurrency_key(prefix: &str, queue_name: &str, job_type: &str) -> String {
+    format!("{prefix}:{queue_name}:running:{job_type}")
+}
+
+// schedule      - zset: {mid run-at-ms} ; Delayed mids, moved to mids-ready when due
+#[inline]
+pub(crate) fn schedule_key(prefix: &str, queue_name: &str) -> ArcString {
+    format!("{prefix}:{queue_name}:schedule").into()
+}
+
+// schedule (legacy) - zset: {mid run-at-ms} ; Schedule shared by all queues of a
+//                                            prefix before it moved per queue,
+//                                            drained by dequeue-at
 #[inline]
-pub(crate) fn schedule_key(prefix: &str) -> ArcString {
+pub(crate) fn legacy_schedule_key(prefix: &str) -> ArcString {
     format!("{prefix}:schedule").into()
 }
@@ -0,0 +1,89 @@
+use crate::helper::read_redis_value_as_str;
+use crate::Queue;
+use redis::{FromRedisValue, RedisResult, Script, ScriptInvocation};
+
+// Hands a locked mid back to the queue without waiting for the lock to expire,
+// for workers shutting down
+#[derive(Clone)]
+pub struct ReleaseAction {
+    script: Script,
+    queue: Queue,
+}
+
+impl ReleaseAction {
+    pub fn new(queue: Queue) -> Self {
+        Self {
+            script: Script::new(crate::lua::RELEASE),
+            queue,
+        }
+    }
+
+    // The attempt is given back unless the handler `started`, `token` of the
+    // dequeue that acquired the lock
+    pub fn prepare_invoke(
+        &self,
+        mid: i64,
+        token: i64,
+        job_type: &str,
+        now: i64,
+        started: bool,
+    ) -> ScriptInvocation<'_> {
+        let mut invoke = self.script.prepare_invoke();
+        invoke
+            .key(self.queue.messages_key.as_str())
+            .key(self.queue.locks_key.as_str())
+            .key(self.queue.attempts_key.as_str())
+            .key(self.queue.done_key.as_str())
+            .key(self.queue.concurrency_key(job_type))
+            .key(self.queue.isleep_a_key.as_str())
+            .key(self.queue.isleep_b_key.as_str())
+            .key(self.queue.lock_tokens_key.as_str());
+
+        invoke.arg(mid).arg(now).arg(i64::from(started)).arg(token);
+
+        invoke
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReleaseStatus {
+    Released,
+    Expired,
+    // Held by a later dequeue
+    Lost,
+    Missing,
+    Unknown(String),
+}
+
+impl TryFrom<&[redis::Value]> for ReleaseStatus {
+    type Error = redis::RedisError;
+
+    fn try_from(values: &[redis::Value]) -> Result<Self, Self::Error> {
+        let mut iter = values.iter();
+        let action =
+            read_redis_value_as_str(iter.next(), "invalid release status - invalid action")?;
+
+        let status = match action.as_ref() {
+            "released" => ReleaseStatus::Released,
+            "expired" => ReleaseStatus::Expired,
+            "lost" => ReleaseStatus::Lost,
+            "missing" => ReleaseStatus::Missing,
+            _ => ReleaseStatus::Unknown(format!("{values:?}")),
+        };
+
+        Ok(status)
+    }
+}
+
+impl FromRedisValue for ReleaseStatus {
+    fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+        match v {
+            redis::Value::Bulk(bulk) => ReleaseStatus::try_from(bulk.as_slice()),
+            _ => Err(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "invalid release status - invalid value type",
+                format!("{v:?}"),
+            ))),
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use crate::{Job, Queue, YqError, YqResult};
+
+#[derive(Clone)]
+pub struct ResultAction {
+    queue: Queue,
+}
+
+impl ResultAction {
+    pub fn new(queue: Queue) -> Self {
+        Self { queue }
+    }
+
+    // Invocation returns the serialized output, nil until done or once expired
+    pub fn prepare_get(&self, mid: i64) -> redis::Cmd {
+        redis::Cmd::get(self.queue.result_key(mid))
+    }
+
+    pub fn decode<J: Job>(result: &str) -> YqResult<J::Output> {
+        serde_json::from_str(result).map_err(YqError::DecodeResult)
+    }
+}
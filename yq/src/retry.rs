@@ -0,0 +1,134 @@
+use rand::Rng;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub multiplier: f64,
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl RetryPolicy {
+    // 5 attempts, retried after ~10s, ~20s, ~40s and ~80s
+    pub const DEFAULT: RetryPolicy = RetryPolicy::new(5);
+
+    pub const NONE: RetryPolicy = RetryPolicy::new(1);
+
+    pub const fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            base_delay: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.2,
+        }
+    }
+
+    pub const fn base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub const fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    // Fraction of the delay randomly added or removed, 0.0 - 1.0
+    pub const fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    // Delay of the retry after `attempts` failed attempts, before the jitter
+    pub fn delay(&self, attempts: u32) -> Duration {
+        let factor = self.multiplier.powi(attempts.saturating_sub(1) as i32);
+        Duration::try_from_secs_f64(self.base_delay.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+    }
+
+    // Delay with the jitter, the one fail.lua schedules the retry after
+    pub(crate) fn retry_delay(&self, attempts: u32) -> Duration {
+        let delay = self.delay(attempts).as_secs_f64() * self.jitter_factor();
+        Duration::try_from_secs_f64(delay).unwrap_or(Duration::MAX)
+    }
+
+    pub(crate) fn jitter_factor(&self) -> f64 {
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter > 0.0 {
+            rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter)
+        } else {
+            1.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_delay_doubles() {
+        let delays: Vec<_> = (1..=4)
+            .map(|attempts| RetryPolicy::DEFAULT.delay(attempts))
+            .collect();
+        assert_eq!(delays, [10, 20, 40, 80].map(Duration::from_secs).to_vec());
+    }
+
+    #[test]
+    fn delay_follows_base_and_multiplier() {
+        let policy = RetryPolicy::new(10)
+            .base_delay(Duration::from_millis(500))
+            .multiplier(3.0);
+        assert_eq!(policy.delay(1), Duration::from_millis(500));
+        assert_eq!(policy.delay(3), Duration::from_millis(4500));
+
+        let constant = policy.multiplier(1.0);
+        assert_eq!(constant.delay(7), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn delay_saturates() {
+        assert_eq!(RetryPolicy::DEFAULT.delay(10_000), Duration::MAX);
+    }
+
+    #[test]
+    fn retry_delay_jittered_around_delay() {
+        let policy = RetryPolicy::DEFAULT.jitter(0.2);
+        for _ in 0..1000 {
+            let delay = policy.retry_delay(2);
+            assert!(
+                (Duration::from_secs(16)..=Duration::from_secs(24)).contains(&delay),
+                "{delay:?}"
+            );
+        }
+        assert_eq!(policy.jitter(0.0).retry_delay(3), Duration::from_secs(40));
+        assert_eq!(policy.jitter(0.0).retry_delay(10_000), Duration::MAX);
+    }
+
+    #[test]
+    fn jitter_factor_within_bounds() {
+        let policy = RetryPolicy::DEFAULT.jitter(0.2);
+        for _ in 0..1000 {
+            let factor = policy.jitter_factor();
+            assert!((0.8..=1.2).contains(&factor), "{factor}");
+        }
+    }
+
+    #[test]
+    fn jitter_clamped() {
+        assert_eq!(RetryPolicy::DEFAULT.jitter(0.0).jitter_factor(), 1.0);
+        assert_eq!(RetryPolicy::DEFAULT.jitter(-1.0).jitter_factor(), 1.0);
+
+        let policy = RetryPolicy::DEFAULT.jitter(5.0);
+        for _ in 0..1000 {
+            let factor = policy.jitter_factor();
+            assert!((0.0..=2.0).contains(&factor), "{factor}");
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use crate::helper::{read_redis_value_as_int, read_redis_value_as_str};
+use crate::Queue;
+use redis::{FromRedisValue, RedisResult, Script, ScriptInvocation};
+
+#[derive(Clone)]
+pub struct StatusAction {
+    script: Script,
+    queue: Queue,
+}
+
+impl StatusAction {
+    pub fn new(queue: Queue) -> Self {
+        Self {
+            script: Script::new(crate::lua::STATUS),
+            queue,
+        }
+    }
+
+    pub fn prepare_invoke(&self, mid: i64, now: i64) -> ScriptInvocation<'_> {
+        let mut invoke = self.script.prepare_invoke();
+        invoke
+            .key(self.queue.messages_key.as_str())
+            .key(self.queue.locks_key.as_str())
+            .key(self.queue.done_key.as_str())
+            .key(self.queue.err_key.as_str())
+            .key(self.queue.schedule_key.as_str())
+            .key(self.queue.dead_key.as_str())
+            .key(self.queue.waiting_key.as_str())
+            .key(self.queue.cancelled_key.as_str())
+            .key(self.queue.done_at_key.as_str());
+
+        invoke.arg(mid).arg(now);
+
+        invoke
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobStatus {
+    Scheduled(i64),
+    Queued,
+    // Waiting on the given number of unfinished parents
+    Waiting(i64),
+    Locked(i64),
+    Done,
+    // Last attempt failed, retried at `retry_at`
+    Failed { error: String, retry_at: i64 },
+    Dead,
+    // Cancelled, directly or because a parent died or was cancelled
+    Cancelled,
+    // Never enqueued, or gone past the ttl of results
+    Missing,
+    Unknown(String),
+}
+
+impl TryFrom<&[redis::Value]> for JobStatus {
+    type Error = redis::RedisError;
+
+    fn try_from(values: &[redis::Value]) -> Result<Self, Self::Error> {
+        let mut iter = values.iter();
+        let action = read_redis_value_as_str(iter.next(), "invalid job status - invalid action")?;
+
+        let status = match action.as_ref() {
+            "scheduled" => {
+                let run_at = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid job status - scheduled - invalid run_at",
+                )?;
+                JobStatus::Scheduled(run_at)
+            }
+            "queued" => JobStatus::Queued,
+            "waiting" => {
+                let pending = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid job status - waiting - invalid pending",
+                )?;
+                JobStatus::Waiting(pending)
+            }
+            "locked" => {
+                let until = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid job status - locked - invalid until",
+                )?;
+                JobStatus::Locked(until)
+            }
+            "done" => JobStatus::Done,
+            "failed" => {
+                let error = read_redis_value_as_str(
+                    iter.next(),
+                    "invalid job status - failed - invalid error",
+                )?;
+                let retry_at = read_redis_value_as_int(
+                    iter.next(),
+                    "invalid job status - failed - invalid retry_at",
+                )?;
+                JobStatus::Failed {
+                    error: error.into_owned(),
+                    retry_at,
+                }
+            }
+            "dead" => JobStatus::Dead,
+            "cancelled" => JobStatus::Cancelled,
+            "missing" => JobStatus::Missing,
+            _ => JobStatus::Unknown(format!("{values:?}")),
+        };
+
+        Ok(status)
+    }
+}
+
+impl FromRedisValue for JobStatus {
+    fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+        match v {
+            redis::Value::Bulk(bulk) => JobStatus::try_from(bulk.as_slice()),
+            _ => Err(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "invalid job status - invalid value type",
+                format!("{v:?}"),
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing, unix_timestamp_ms, DequeueAction, DequeueStatus, EnqueueAction, EnqueueOptions,
+        EnqueueStatus, FinishAction, Job, JobLimits, JobType,
+    };
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct Noop;
+
+    impl Job for Noop {
+        const JOB_TYPE: JobType = JobType::Borrowed("noop");
+        type State = ();
+        type Output = ();
+    }
+
+    fn now() -> i64 {
+        unix_timestamp_ms(time::OffsetDateTime::now_utc())
+    }
+
+    #[test]
+    #[ignore = "needs a redis at YQ_TEST_REDIS_URL"]
+    fn done_after_gc() {
+        let mut con = testing::connection();
+        let queue = testing::queue();
+        let job_limits = JobLimits::new();
+
+        let status: EnqueueStatus = EnqueueAction::new(queue.clone())
+            .prepare_invoke(&Noop, &EnqueueOptions::default())
+            .unwrap()
+            .invoke(&mut con)
+            .unwrap();
+        let EnqueueStatus::Added(added) = status else {
+            panic!("{status:?}");
+        };
+
+        let dequeue = DequeueAction::new(queue.clone());
+        let status: DequeueStatus = dequeue
+            .prepare_invoke(now(), &job_limits)
+            .invoke(&mut con)
+            .unwrap();
+        let DequeueStatus::Handle(handle) = status else {
+            panic!("{status:?}");
+        };
+        let _: i64 = FinishAction::new(queue.clone())
+            .prepare_invoke(handle.mid, handle.token, &handle.job_type, "null", now())
+            .invoke(&mut con)
+            .unwrap();
+
+        // Going round the circle gc's the done mid
+        for _ in 0..4 {
+            let _: DequeueStatus = dequeue
+                .prepare_invoke(now(), &job_limits)
+                .invoke(&mut con)
+                .unwrap();
+        }
+        let exists: bool = redis::Cmd::hexists(queue.messages_key.as_str(), added.mid)
+            .query(&mut con)
+            .unwrap();
+        assert!(!exists);
+
+        let status: JobStatus = StatusAction::new(queue)
+            .prepare_invoke(added.mid, now())
+            .invoke(&mut con)
+            .unwrap();
+        assert_eq!(status, JobStatus::Done);
+    }
+}
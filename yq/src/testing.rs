@@ -0,0 +1,19 @@
+use crate::Queue;
+
+// Tests of the scripts need a redis, at YQ_TEST_REDIS_URL or the local default,
+// and are run with `cargo test -- --ignored`. Each gets a queue of its own.
+
+pub(crate) fn connection() -> redis::Connection {
+    let url = std::env::var("YQ_TEST_REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".into());
+    redis::Client::open(url)
+        .and_then(|client| client.get_connection())
+        .expect("redis for tests")
+}
+
+pub(crate) fn queue() -> Queue {
+    Queue::builder()
+        .prefix("yq-test")
+        .queue_name(format!("q{}", rand::random::<u32>()))
+        .build()
+        .unwrap()
+}